@@ -1,10 +1,11 @@
-#![cfg(feature = "sync")]
-
 use std::convert::{AsRef, From};
 use std::default::Default;
 use std::f64::consts::PI;
+#[cfg(feature = "sync")]
 use std::io::BufReader;
 
+#[cfg(feature = "sync")]
+use criterion::BenchmarkId;
 use criterion::{criterion_group, criterion_main, Criterion};
 use hepmc2::{Reader, Writer};
 use rand::distributions::{Alphanumeric, Distribution, Standard};
@@ -25,6 +26,7 @@ impl Distribution<Event> for Standard {
             alpha_qed: 1. / 137.,
             energy_unit: Default::default(),
             length_unit: Default::default(),
+            units_declared: true,
             mpi: rng.gen(),
             number: rng.gen(),
             pdf_info: rng.gen::<PdfInfo>().into(),
@@ -35,6 +37,7 @@ impl Distribution<Event> for Standard {
             scale: rng.gen(),
             signal_process_id: rng.gen(),
             signal_process_vertex: rng.gen(),
+            beam_barcodes: rng.gen(),
             vertices: {
                 let len = rng.gen_range(1..6);
                 (0..len).map(|_| rng.gen::<Vertex>().into()).collect()
@@ -49,6 +52,7 @@ impl Distribution<Event> for Standard {
             },
             xs: rng.gen::<CrossSection>().into(),
             heavy_ion_info: None,
+            raw_extra: Vec::new(),
         })
     }
 }
@@ -117,6 +121,7 @@ impl From<Particle> for hepmc2::event::Particle {
 impl Distribution<Particle> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Particle {
         Particle(hepmc2::event::Particle {
+            barcode: 0,
             end_vtx: 0,
             flows: Default::default(),
             id: rng.gen_range(-30..30),
@@ -170,12 +175,17 @@ fn gen_random_name<R: Rng>(rng: &mut R) -> String {
 
 const NEVENTS: usize = 3_000;
 
+fn gen_events() -> Vec<Event> {
+    let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+    (0..NEVENTS).map(|_| rng.gen()).collect()
+}
+
+#[cfg(feature = "sync")]
 fn criterion_benchmark(c: &mut Criterion) {
     let mut buf: Vec<u8> = Vec::new();
 
     {
-        let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
-        let events: Vec<Event> = (0..NEVENTS).map(|_| rng.gen()).collect();
+        let events = gen_events();
         c.bench_function("write", |b| {
             b.iter(|| {
                 buf.clear();
@@ -199,7 +209,318 @@ fn criterion_benchmark(c: &mut Criterion) {
             assert_eq!(count, NEVENTS)
         })
     });
+
+    c.bench_function("read_into", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            let buf = BufReader::new(buf.as_slice());
+            let mut reader = Reader::new(buf);
+            let mut event = Default::default();
+            while reader.read_into(&mut event).unwrap() {
+                count += 1
+            }
+            assert_eq!(count, NEVENTS)
+        })
+    });
+
+    {
+        // the same weight names in every event, as in typical files
+        let names: Vec<_> = (0..30).map(|i| format!("weight_{i}")).collect();
+        let mut named = Vec::new();
+        let mut writer = Writer::new(&mut named).unwrap();
+        for mut event in gen_events() {
+            event.0.weight_names = names.clone().into();
+            event.0.weights.resize(names.len(), 1.);
+            writer.write(event.as_ref()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut group = c.benchmark_group("weight_names");
+        for intern in [false, true] {
+            let id = if intern { "interned" } else { "owned" };
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    let mut count = 0;
+                    let buf = BufReader::new(named.as_slice());
+                    let mut reader = Reader::new(buf);
+                    reader.set_intern_weight_names(intern);
+                    for _event in reader {
+                        count += 1
+                    }
+                    assert_eq!(count, NEVENTS)
+                })
+            });
+        }
+        group.finish();
+    }
+
+    #[cfg(feature = "mmap")]
+    {
+        let path = std::env::temp_dir().join("hepmc2-bench.hepmc2");
+        std::fs::write(&path, &buf).unwrap();
+        c.bench_function("read_file", |b| {
+            b.iter(|| {
+                let file = std::fs::File::open(&path).unwrap();
+                let reader = Reader::new(BufReader::new(file));
+                assert_eq!(reader.count(), NEVENTS)
+            })
+        });
+        c.bench_function("read_mmap", |b| {
+            b.iter(|| {
+                let reader = unsafe { Reader::mmap(&path) }.unwrap();
+                assert_eq!(reader.count(), NEVENTS)
+            })
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    let mut group = c.benchmark_group("read_with_capacity");
+    for capacity in [64, 256, 4096, 65536] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(capacity),
+            &capacity,
+            |b, &capacity| {
+                b.iter(|| {
+                    let mut count = 0;
+                    let buf = BufReader::new(buf.as_slice());
+                    let reader = Reader::with_capacity(buf, capacity);
+                    for _event in reader {
+                        count += 1
+                    }
+                    assert_eq!(count, NEVENTS)
+                })
+            },
+        );
+    }
+    group.finish();
+
+    c.bench_function("round_trip", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            out.clear();
+            assert_eq!(round_trip(&buf, &mut out), NEVENTS)
+        })
+    });
+}
+
+/// Read all events from `input` and write them to `out`
+///
+/// Returns the number of events.
+#[cfg(feature = "sync")]
+fn round_trip(input: &[u8], out: &mut Vec<u8>) -> usize {
+    let reader = Reader::new(BufReader::new(input));
+    let mut writer = Writer::new(out).unwrap();
+    let count = writer.write_all(reader.map(Result::unwrap)).unwrap();
+    writer.finish().unwrap();
+    count
+}
+
+#[cfg(feature = "sync")]
+const NLARGE_EVENTS: usize = 100;
+
+/// Events with two vertices, each with `nparticles` outgoing particles
+#[cfg(feature = "sync")]
+fn gen_large_events(nparticles: usize) -> Vec<Event> {
+    let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+    (0..NLARGE_EVENTS)
+        .map(|_| {
+            let mut event: Event = rng.gen();
+            event.0.vertices = (0..2)
+                .map(|_| {
+                    let mut vertex: hepmc2::event::Vertex =
+                        rng.gen::<Vertex>().into();
+                    vertex.particles_out = (0..nparticles)
+                        .map(|_| rng.gen::<Particle>().into())
+                        .collect();
+                    vertex
+                })
+                .collect();
+            event
+        })
+        .collect()
+}
+
+#[cfg(feature = "sync")]
+fn large_event_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_events");
+    for nparticles in [100, 300, 1000] {
+        let mut buf = Vec::new();
+        let mut writer = Writer::new(&mut buf).unwrap();
+        let events = gen_large_events(nparticles);
+        writer.write_all(events.iter().map(Event::as_ref)).unwrap();
+        writer.finish().unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("write", nparticles),
+            &events,
+            |b, events| {
+                let mut out = Vec::new();
+                b.iter(|| {
+                    out.clear();
+                    let mut writer = Writer::new(&mut out).unwrap();
+                    for event in events {
+                        writer.write(event.as_ref()).unwrap()
+                    }
+                    writer.finish().unwrap()
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read", nparticles),
+            &buf,
+            |b, buf| {
+                b.iter(|| {
+                    let reader = Reader::new(BufReader::new(buf.as_slice()));
+                    assert_eq!(reader.count(), NLARGE_EVENTS)
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("round_trip", nparticles),
+            &buf,
+            |b, buf| {
+                let mut out = Vec::new();
+                b.iter(|| {
+                    out.clear();
+                    assert_eq!(round_trip(buf, &mut out), NLARGE_EVENTS)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+#[cfg(feature = "sync")]
+fn threaded_write_benchmark(c: &mut Criterion) {
+    use hepmc2::threaded::ThreadedWriter;
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    let path = std::env::temp_dir()
+        .join(format!("hepmc2-bench-{}.hepmc2", std::process::id()));
+    let mut group = c.benchmark_group("write_file");
+    for nparticles in [100, 1000] {
+        let events = gen_large_events(nparticles);
+        group.bench_with_input(
+            BenchmarkId::new("plain", nparticles),
+            &events,
+            |b, events| {
+                b.iter(|| {
+                    let file = BufWriter::new(File::create(&path).unwrap());
+                    let mut writer = Writer::new(file).unwrap();
+                    for event in events {
+                        writer.write(event.as_ref()).unwrap()
+                    }
+                    writer.finish().unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("threaded", nparticles),
+            &events,
+            |b, events| {
+                b.iter(|| {
+                    let file = BufWriter::new(File::create(&path).unwrap());
+                    let mut writer = ThreadedWriter::new(file).unwrap();
+                    for event in events {
+                        writer.write(event.as_ref()).unwrap()
+                    }
+                    writer.finish().unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+    // the file does not exist if the benchmarks were filtered out
+    let _ = std::fs::remove_file(path);
+}
+
+fn float_benchmark(c: &mut Criterion) {
+    let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+    let floats: Vec<String> = (0..NEVENTS)
+        .map(|_| format!("{:e}", rng.gen::<f64>() * 1e3))
+        .collect();
+
+    let mut group = c.benchmark_group("parse_float");
+    group.bench_function("nom", |b| {
+        b.iter(|| {
+            for float in &floats {
+                let res =
+                    nom::number::complete::double::<_, ()>(float.as_str());
+                assert!(res.is_ok());
+            }
+        })
+    });
+    #[cfg(feature = "fast-float")]
+    group.bench_function("fast-float", |b| {
+        b.iter(|| {
+            for float in &floats {
+                let res = fast_float2::parse_partial::<f64, _>(float);
+                assert!(res.is_ok());
+            }
+        })
+    });
+    group.finish();
+}
+
+#[cfg(feature = "tokio")]
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+#[cfg(feature = "tokio")]
+async fn write_events(buf: &mut Vec<u8>, events: &[Event]) {
+    let mut writer = Writer::new(buf).await.unwrap();
+    for event in events {
+        writer.write(event.as_ref()).await.unwrap()
+    }
+    writer.finish().await.unwrap()
+}
+
+#[cfg(feature = "tokio")]
+fn async_write(c: &mut Criterion) {
+    let rt = runtime();
+    let events = gen_events();
+    let mut buf: Vec<u8> = Vec::new();
+    c.bench_function("async_write", |b| {
+        b.iter(|| {
+            buf.clear();
+            rt.block_on(write_events(&mut buf, &events))
+        })
+    });
+}
+
+#[cfg(feature = "tokio")]
+fn async_read(c: &mut Criterion) {
+    let rt = runtime();
+    let mut buf: Vec<u8> = Vec::new();
+    rt.block_on(write_events(&mut buf, &gen_events()));
+    c.bench_function("async_read", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let mut count = 0;
+                let mut reader = Reader::new(buf.as_slice());
+                while let Some(event) = reader.next().await {
+                    event.unwrap();
+                    count += 1
+                }
+                assert_eq!(count, NEVENTS)
+            })
+        })
+    });
 }
 
-criterion_group!(benches, criterion_benchmark);
+#[cfg(feature = "sync")]
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    large_event_benchmark,
+    threaded_write_benchmark,
+    float_benchmark
+);
+#[cfg(feature = "tokio")]
+criterion_group!(benches, async_write, async_read, float_benchmark);
 criterion_main!(benches);
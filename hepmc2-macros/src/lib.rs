@@ -4,8 +4,8 @@ use syn::{
     TypeParamBound,
 };
 
-/// Adds a trait bound to every generic parameter in an impl block or struct
-/// definition
+/// Adds a trait bound to every generic parameter in an impl block, struct, or
+/// enum definition
 fn add_trait_bound(
     mut item: Item,
     traits: &[TypeParamBound],
@@ -20,10 +20,15 @@ fn add_trait_bound(
             .iter()
             .for_each(|t| append_generic(&mut struct_item.generics, t));
         Ok(item)
+    } else if let Item::Enum(ref mut enum_item) = item {
+        traits
+            .iter()
+            .for_each(|t| append_generic(&mut enum_item.generics, t));
+        Ok(item)
     } else {
         Err(Error::new(
             Span::call_site().into(),
-            "macro must be called on impl block or struct definition",
+            "macro must be called on impl block, struct, or enum definition",
         ))
     }
 }
@@ -52,8 +57,8 @@ fn feature_check() -> Result<(), Error> {
 
 /// Adds the trait bounds required for either sync or async writing
 ///
-/// These bounds are applied to all generic parameters in either an impl block or
-/// struct definition.
+/// These bounds are applied to all generic parameters in an impl block, struct,
+/// or enum definition.
 ///
 /// Traits are chosen according to which features are enabled.
 #[proc_macro_attribute]
@@ -80,8 +85,8 @@ pub fn write_bound(_: TokenStream, item: TokenStream) -> TokenStream {
 
 /// Adds the trait bounds required for either sync or async reading
 ///
-/// These bounds are applied to all generic parameters in either an impl block or
-/// struct definition.
+/// These bounds are applied to all generic parameters in an impl block, struct,
+/// or enum definition.
 ///
 /// Traits are chosen according to which features are enabled.
 #[proc_macro_attribute]
@@ -105,3 +110,31 @@ pub fn read_bound(_: TokenStream, item: TokenStream) -> TokenStream {
         Err(e) => Error::into_compile_error(e).into(),
     }
 }
+
+/// Adds the trait bounds required for either sync or async seeking
+///
+/// These bounds are applied to all generic parameters in an impl block, struct,
+/// or enum definition.
+///
+/// Traits are chosen according to which features are enabled.
+#[proc_macro_attribute]
+pub fn seek_bound(_: TokenStream, item: TokenStream) -> TokenStream {
+    if let Err(e) = feature_check() {
+        return Error::into_compile_error(e).into();
+    }
+    let seek_trait = if cfg!(feature = "sync") {
+        vec![parse_quote!(::std::io::Seek)]
+    } else if cfg!(feature = "tokio") {
+        vec![
+            parse_quote!(::tokio::io::AsyncSeekExt),
+            parse_quote!(::std::marker::Unpin),
+        ]
+    } else {
+        unreachable!()
+    };
+    let item = add_trait_bound(parse_macro_input!(item as Item), &seek_trait);
+    match item {
+        Ok(item) => quote::quote!(#item).into(),
+        Err(e) => Error::into_compile_error(e).into(),
+    }
+}
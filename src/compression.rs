@@ -0,0 +1,425 @@
+//! Transparent handling of compressed input and output
+//!
+//! Support for the individual compression formats is enabled with
+//! the corresponding crate features:
+//!
+//! | Format | Feature  |
+//! |--------|----------|
+//! | gzip   | `flate2` |
+//! | zstd   | `zstd`   |
+//! | xz     | `xz`     |
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncWriteExt;
+
+#[cfg(all(
+    any(feature = "flate2", feature = "zstd", feature = "xz"),
+    feature = "sync"
+))]
+use std::io::BufReader;
+#[cfg(all(
+    any(feature = "flate2", feature = "zstd", feature = "xz"),
+    feature = "tokio"
+))]
+use tokio::io::BufReader;
+
+#[cfg(feature = "sync")]
+mod codecs {
+    #[cfg(feature = "flate2")]
+    pub(super) type GzDecoder<T> = flate2::bufread::MultiGzDecoder<T>;
+    #[cfg(feature = "flate2")]
+    pub(super) type GzEncoder<T> = flate2::write::GzEncoder<T>;
+    #[cfg(feature = "zstd")]
+    pub(super) type ZstdDecoder<T> = zstd::stream::read::Decoder<'static, T>;
+    #[cfg(feature = "zstd")]
+    pub(super) type ZstdEncoder<T> = zstd::stream::write::Encoder<'static, T>;
+    #[cfg(feature = "xz")]
+    pub(super) type XzDecoder<T> = xz2::bufread::XzDecoder<T>;
+    #[cfg(feature = "xz")]
+    pub(super) type XzEncoder<T> = xz2::write::XzEncoder<T>;
+}
+
+#[cfg(feature = "tokio")]
+mod codecs {
+    #[cfg(feature = "flate2")]
+    pub(super) type GzDecoder<T> =
+        async_compression::tokio::bufread::GzipDecoder<T>;
+    #[cfg(feature = "flate2")]
+    pub(super) type GzEncoder<T> =
+        async_compression::tokio::write::GzipEncoder<T>;
+    #[cfg(feature = "zstd")]
+    pub(super) type ZstdDecoder<T> =
+        async_compression::tokio::bufread::ZstdDecoder<T>;
+    #[cfg(feature = "zstd")]
+    pub(super) type ZstdEncoder<T> =
+        async_compression::tokio::write::ZstdEncoder<T>;
+    #[cfg(feature = "xz")]
+    pub(super) type XzDecoder<T> =
+        async_compression::tokio::bufread::XzDecoder<T>;
+    #[cfg(feature = "xz")]
+    pub(super) type XzEncoder<T> =
+        async_compression::tokio::write::XzEncoder<T>;
+}
+
+#[allow(unused_imports)]
+use codecs::*;
+
+#[cfg(feature = "flate2")]
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+#[cfg(feature = "xz")]
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+/// Compression format for output
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Copy, Clone)]
+#[non_exhaustive]
+pub enum Compression {
+    /// No compression
+    #[default]
+    None,
+    /// gzip compression
+    #[cfg(feature = "flate2")]
+    Gzip,
+    /// zstd compression
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// xz compression
+    #[cfg(feature = "xz")]
+    Xz,
+}
+
+impl Compression {
+    /// Select the compression format from the extension of `path`
+    ///
+    /// Recognised extensions are `gz`, `zst`, and `xz`. It is an
+    /// error if the feature for the corresponding format is not
+    /// enabled. Any other extension means no compression.
+    pub(crate) fn from_path(path: &Path) -> Result<Self, io::Error> {
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        match ext {
+            #[cfg(feature = "flate2")]
+            Some("gz") => Ok(Self::Gzip),
+            #[cfg(not(feature = "flate2"))]
+            Some("gz") => Err(missing_feature("gzip", "flate2")),
+            #[cfg(feature = "zstd")]
+            Some("zst") => Ok(Self::Zstd),
+            #[cfg(not(feature = "zstd"))]
+            Some("zst") => Err(missing_feature("zstd", "zstd")),
+            #[cfg(feature = "xz")]
+            Some("xz") => Ok(Self::Xz),
+            #[cfg(not(feature = "xz"))]
+            Some("xz") => Err(missing_feature("xz", "xz")),
+            _ => Ok(Self::None),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "flate2", feature = "zstd", feature = "xz")))]
+fn missing_feature(format: &str, feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{format} compression requires the '{feature}' feature"),
+    )
+}
+
+/// Input stream that is decompressed on the fly if necessary
+#[non_exhaustive]
+pub enum Decompressor<T> {
+    /// Uncompressed input
+    Plain(T),
+    /// gzip-compressed input
+    #[cfg(feature = "flate2")]
+    Gzip(BufReader<GzDecoder<T>>),
+    /// zstd-compressed input
+    #[cfg(feature = "zstd")]
+    Zstd(BufReader<ZstdDecoder<T>>),
+    /// xz-compressed input
+    #[cfg(feature = "xz")]
+    Xz(BufReader<XzDecoder<T>>),
+}
+
+/// Output stream that is compressed on the fly
+#[hepmc2_macros::write_bound]
+#[non_exhaustive]
+pub enum Compressor<T> {
+    /// Uncompressed output
+    Plain(T),
+    /// gzip-compressed output
+    #[cfg(feature = "flate2")]
+    Gzip(GzEncoder<T>),
+    /// zstd-compressed output
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdEncoder<T>),
+    /// xz-compressed output
+    #[cfg(feature = "xz")]
+    Xz(XzEncoder<T>),
+}
+
+/// Apply the same expression to the inner stream of any variant
+macro_rules! dispatch {
+    ($type: ident, $self: expr, $s: ident => $e: expr) => {
+        match $self {
+            $type::Plain($s) => $e,
+            #[cfg(feature = "flate2")]
+            $type::Gzip($s) => $e,
+            #[cfg(feature = "zstd")]
+            $type::Zstd($s) => $e,
+            #[cfg(feature = "xz")]
+            $type::Xz($s) => $e,
+        }
+    };
+}
+
+impl<T> Decompressor<T> {
+    /// The detected compression format
+    pub fn compression(&self) -> Compression {
+        match self {
+            Self::Plain(_) => Compression::None,
+            #[cfg(feature = "flate2")]
+            Self::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => Compression::Zstd,
+            #[cfg(feature = "xz")]
+            Self::Xz(_) => Compression::Xz,
+        }
+    }
+
+    /// Whether the input is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compression() != Compression::None
+    }
+}
+
+impl<T> std::fmt::Debug for Decompressor<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decompressor")
+            .field("compression", &self.compression())
+            .finish_non_exhaustive()
+    }
+}
+
+#[hepmc2_macros::read_bound]
+impl<T> Decompressor<T> {
+    /// Detect the compression format of `stream`
+    ///
+    /// The format is identified by peeking at the first bytes of the
+    /// stream. If no compression is detected, the input is passed on
+    /// unchanged.
+    #[maybe_async::maybe_async]
+    pub async fn new(mut stream: T) -> Result<Self, io::Error> {
+        let _magic = stream.fill_buf().await?;
+        #[cfg(feature = "flate2")]
+        if _magic.starts_with(GZIP_MAGIC) {
+            // concatenated gzip members form a single stream
+            #[allow(unused_mut)]
+            let mut decoder = GzDecoder::new(stream);
+            #[cfg(feature = "tokio")]
+            decoder.multiple_members(true);
+            return Ok(Self::Gzip(BufReader::new(decoder)));
+        }
+        #[cfg(feature = "zstd")]
+        if _magic.starts_with(ZSTD_MAGIC) {
+            #[cfg(feature = "sync")]
+            let decoder = ZstdDecoder::with_buffer(stream)?;
+            #[cfg(feature = "tokio")]
+            let decoder = ZstdDecoder::new(stream);
+            return Ok(Self::Zstd(BufReader::new(decoder)));
+        }
+        #[cfg(feature = "xz")]
+        if _magic.starts_with(XZ_MAGIC) {
+            let decoder = XzDecoder::new(stream);
+            return Ok(Self::Xz(BufReader::new(decoder)));
+        }
+        Ok(Self::Plain(stream))
+    }
+}
+
+#[hepmc2_macros::write_bound]
+impl<T> Compressor<T> {
+    /// Compress output to `stream` in the given format
+    pub fn new(stream: T, compression: Compression) -> Result<Self, io::Error> {
+        let compressor = match compression {
+            Compression::None => Self::Plain(stream),
+            #[cfg(all(feature = "flate2", feature = "sync"))]
+            Compression::Gzip => Self::Gzip(GzEncoder::new(
+                stream,
+                flate2::Compression::default(),
+            )),
+            #[cfg(all(feature = "flate2", feature = "tokio"))]
+            Compression::Gzip => Self::Gzip(GzEncoder::new(stream)),
+            #[cfg(all(feature = "zstd", feature = "sync"))]
+            Compression::Zstd => Self::Zstd(ZstdEncoder::new(stream, 0)?),
+            #[cfg(all(feature = "zstd", feature = "tokio"))]
+            Compression::Zstd => Self::Zstd(ZstdEncoder::new(stream)),
+            #[cfg(all(feature = "xz", feature = "sync"))]
+            Compression::Xz => Self::Xz(XzEncoder::new(stream, 6)),
+            #[cfg(all(feature = "xz", feature = "tokio"))]
+            Compression::Xz => Self::Xz(XzEncoder::new(stream)),
+        };
+        Ok(compressor)
+    }
+
+    /// Write all pending output and finish the compressed stream
+    ///
+    /// No further output should be written afterwards.
+    #[maybe_async::sync_impl]
+    pub fn try_finish(&mut self) -> Result<(), io::Error> {
+        match self {
+            Self::Plain(s) => s.flush(),
+            #[cfg(feature = "flate2")]
+            Self::Gzip(s) => {
+                s.try_finish()?;
+                s.get_mut().flush()
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd(s) => {
+                s.do_finish()?;
+                s.get_mut().flush()
+            }
+            #[cfg(feature = "xz")]
+            Self::Xz(s) => {
+                s.try_finish()?;
+                s.get_mut().flush()
+            }
+        }
+    }
+
+    /// Write all pending output and finish the compressed stream
+    ///
+    /// No further output should be written afterwards.
+    #[maybe_async::async_impl]
+    pub async fn try_finish(&mut self) -> Result<(), io::Error> {
+        self.shutdown().await
+    }
+}
+
+#[hepmc2_macros::write_bound]
+impl<T> Compressor<T> {
+    /// The compression format
+    pub fn compression(&self) -> Compression {
+        match self {
+            Self::Plain(_) => Compression::None,
+            #[cfg(feature = "flate2")]
+            Self::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => Compression::Zstd,
+            #[cfg(feature = "xz")]
+            Self::Xz(_) => Compression::Xz,
+        }
+    }
+}
+
+#[hepmc2_macros::write_bound]
+impl<T> std::fmt::Debug for Compressor<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Compressor")
+            .field("compression", &self.compression())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T: io::BufRead> io::Read for Decompressor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        dispatch!(Decompressor, self, s => s.read(buf))
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T: io::BufRead> io::BufRead for Decompressor<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        dispatch!(Decompressor, self, s => s.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        dispatch!(Decompressor, self, s => s.consume(amt))
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T: io::Write> io::Write for Compressor<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        dispatch!(Compressor, self, s => s.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        dispatch!(Compressor, self, s => s.flush())
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod async_impls {
+    use super::{Compressor, Decompressor};
+
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+    impl<T: AsyncBufRead + Unpin> AsyncRead for Decompressor<T> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            dispatch!(
+                Decompressor,
+                self.get_mut(),
+                s => Pin::new(s).poll_read(cx, buf)
+            )
+        }
+    }
+
+    impl<T: AsyncBufRead + Unpin> AsyncBufRead for Decompressor<T> {
+        fn poll_fill_buf(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<&[u8]>> {
+            dispatch!(
+                Decompressor,
+                self.get_mut(),
+                s => Pin::new(s).poll_fill_buf(cx)
+            )
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            dispatch!(Decompressor, self.get_mut(), s => Pin::new(s).consume(amt))
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> AsyncWrite for Compressor<T> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            dispatch!(
+                Compressor,
+                self.get_mut(),
+                s => Pin::new(s).poll_write(cx, buf)
+            )
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            dispatch!(Compressor, self.get_mut(), s => Pin::new(s).poll_flush(cx))
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            dispatch!(
+                Compressor,
+                self.get_mut(),
+                s => Pin::new(s).poll_shutdown(cx)
+            )
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Export of particle tables as CSV
+//!
+//! This module is only available with the `csv` feature.
+//! [write_particles_csv] writes one row per final-state particle with
+//! the columns
+//!
+//! `event_number,pdg_id,status,px,py,pz,e,m`
+//!
+//! preceded by a single header row. The delimiter and the selection
+//! of particles can be changed with [CsvOptions].
+use std::io::{self, Write};
+
+use crate::event::Event;
+use crate::writer::Float;
+
+const COLUMNS: [&str; 8] = [
+    "event_number",
+    "pdg_id",
+    "status",
+    "px",
+    "py",
+    "pz",
+    "e",
+    "m",
+];
+
+/// Options for CSV export
+///
+/// # Example
+///
+/// ```
+/// use hepmc2::csv::CsvOptions;
+/// use hepmc2::event::Event;
+///
+/// let events = [Event::default()];
+/// let options = CsvOptions::new().delimiter('\t').all_particles(true);
+/// let mut output = Vec::new();
+/// hepmc2::csv::write_particles_csv_with(&events, &mut output, &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CsvOptions {
+    delimiter: char,
+    all_particles: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            all_particles: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Construct new `CsvOptions` with default settings
+    ///
+    /// By default, the delimiter is a comma and only final-state
+    /// particles are written.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the delimiter between columns
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set whether all particles are written instead of only the
+    /// final-state ones
+    pub fn all_particles(mut self, all_particles: bool) -> Self {
+        self.all_particles = all_particles;
+        self
+    }
+}
+
+/// Write the final-state particles of `events` as CSV
+///
+/// See the [module documentation](self) for the format.
+pub fn write_particles_csv<'a, I, W>(events: I, out: W) -> Result<(), io::Error>
+where
+    I: IntoIterator<Item = &'a Event>,
+    W: Write,
+{
+    write_particles_csv_with(events, out, &CsvOptions::default())
+}
+
+/// Write the particles of `events` as CSV with the given options
+pub fn write_particles_csv_with<'a, I, W>(
+    events: I,
+    mut out: W,
+    options: &CsvOptions,
+) -> Result<(), io::Error>
+where
+    I: IntoIterator<Item = &'a Event>,
+    W: Write,
+{
+    let delim = options.delimiter;
+    for (n, column) in COLUMNS.iter().enumerate() {
+        if n > 0 {
+            write!(out, "{delim}")?;
+        }
+        out.write_all(column.as_bytes())?;
+    }
+    writeln!(out)?;
+    for event in events {
+        for particle in event.particles() {
+            if !options.all_particles && !particle.is_final() {
+                continue;
+            }
+            let p = &particle.p;
+            write!(
+                out,
+                "{}{delim}{}{delim}{}",
+                event.number, particle.id, particle.status
+            )?;
+            for value in [p.px(), p.py(), p.pz(), p.e(), particle.m] {
+                write!(out, "{delim}{}", Float::shortest(value))?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
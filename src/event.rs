@@ -1,10 +1,14 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use strum::EnumString;
+use thiserror::Error;
 
 /// Scattering event
-#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Event {
     pub number: i32,
     pub mpi: i32,
@@ -13,19 +17,881 @@ pub struct Event {
     pub alpha_qed: f64,
     pub signal_process_id: i32,
     pub signal_process_vertex: i32,
-    pub random_states: Vec<i32>,
+    /// Barcodes of the two beam particles
+    ///
+    /// A barcode of zero means that there is no such particle. See
+    /// also [beam_particles](Self::beam_particles).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub beam_barcodes: [i32; 2],
+    /// Random number generator states
+    ///
+    /// HepMC2 stores these as `long`, which has 64 bits on most
+    /// platforms.
+    pub random_states: Vec<i64>,
     pub weights: Vec<f64>,
-    pub weight_names: Vec<String>,
+    pub weight_names: WeightNames,
     pub vertices: Vec<Vertex>,
     pub xs: CrossSection,
     pub pdf_info: PdfInfo,
     pub energy_unit: EnergyUnit,
     pub length_unit: LengthUnit,
+    /// Whether the units were given explicitly in a `U` line
+    ///
+    /// If not, `energy_unit` and `length_unit` are the defaults. This
+    /// is `true` for events that were not read from a stream. The
+    /// [Writer](crate::Writer) always writes the units.
+    #[cfg_attr(feature = "serde", serde(default = "units_declared_default"))]
+    pub units_declared: bool,
     pub heavy_ion_info: Option<HeavyIonInfo>,
+    /// Lines with an unrecognised prefix
+    ///
+    /// See [UnknownLinePolicy](crate::reader::UnknownLinePolicy).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub raw_extra: Vec<String>,
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self {
+            number: Default::default(),
+            mpi: Default::default(),
+            scale: Default::default(),
+            alpha_qcd: Default::default(),
+            alpha_qed: Default::default(),
+            signal_process_id: Default::default(),
+            signal_process_vertex: Default::default(),
+            beam_barcodes: Default::default(),
+            random_states: Default::default(),
+            weights: Default::default(),
+            weight_names: Default::default(),
+            vertices: Default::default(),
+            xs: Default::default(),
+            pdf_info: Default::default(),
+            energy_unit: Default::default(),
+            length_unit: Default::default(),
+            units_declared: units_declared_default(),
+            heavy_ion_info: Default::default(),
+            raw_extra: Default::default(),
+        }
+    }
+}
+
+fn units_declared_default() -> bool {
+    true
+}
+
+impl Event {
+    /// Construct an event with the given number
+    ///
+    /// All other fields take their default values. Vertices, weights,
+    /// and the cross section can be added with
+    /// [with_vertex](Self::with_vertex),
+    /// [with_weight](Self::with_weight), and
+    /// [with_cross_section](Self::with_cross_section).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::{CrossSection, Event, Vertex};
+    ///
+    /// let xs = CrossSection {
+    ///     cross_section: 1.5,
+    ///     cross_section_error: 0.1,
+    /// };
+    /// let event = Event::new(1)
+    ///     .with_vertex(Vertex::default())
+    ///     .with_weight(0.5)
+    ///     .with_cross_section(xs);
+    /// assert_eq!(event.weights, [0.5]);
+    /// ```
+    pub fn new(number: i32) -> Self {
+        Self {
+            number,
+            ..Default::default()
+        }
+    }
+
+    /// Add a vertex
+    pub fn with_vertex(mut self, vertex: Vertex) -> Self {
+        self.vertices.push(vertex);
+        self
+    }
+
+    /// Add a weight
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weights.push(weight);
+        self
+    }
+
+    /// Set the cross section
+    pub fn with_cross_section(mut self, xs: CrossSection) -> Self {
+        self.xs = xs;
+        self
+    }
+
+    /// The weight with the given name
+    ///
+    /// Returns `None` if there is no weight name `name` or if there
+    /// is no weight at the position of the name.
+    pub fn weight(&self, name: &str) -> Option<f64> {
+        let pos = self.weight_names.iter().position(|n| n == name)?;
+        self.weights.get(pos).copied()
+    }
+
+    /// Set the weight with the given name
+    ///
+    /// If there is no weight name `name` yet, it is appended to the
+    /// weight names and `value` to the weights. Before appending, the
+    /// shorter of the two is padded so that names and weights stay
+    /// aligned: unnamed weights are named after their index, as in
+    /// HepMC2, and missing weights are set to zero.
+    pub fn set_weight(&mut self, name: &str, value: f64) {
+        if let Some(pos) = self.weight_names.iter().position(|n| n == name) {
+            if pos >= self.weights.len() {
+                self.weights.resize(pos + 1, 0.);
+            }
+            self.weights[pos] = value;
+            return;
+        }
+        let len = self.weights.len().max(self.weight_names.len());
+        for idx in self.weight_names.len()..len {
+            self.weight_names.push(idx.to_string());
+        }
+        self.weights.resize(len, 0.);
+        self.weight_names.push(name.to_owned());
+        self.weights.push(value);
+    }
+
+    /// Make the numbers of weights and weight names agree
+    ///
+    /// Nothing is done if the event has no weight names at all or if
+    /// the numbers already agree. Otherwise, the weights and names
+    /// are adjusted according to `policy`, see [WeightRepair] for
+    /// details.
+    pub fn reconcile_weights(&mut self, policy: WeightRepair) {
+        let nnames = self.weight_names.len();
+        if nnames == 0 || nnames == self.weights.len() {
+            return;
+        }
+        match policy {
+            WeightRepair::TruncateToNames => {
+                let len = self.weights.len().min(nnames);
+                self.weights.truncate(len);
+                self.weight_names.truncate(len);
+            }
+            WeightRepair::PadNames(prefix) => {
+                self.weight_names.truncate(self.weights.len());
+                for idx in nnames..self.weights.len() {
+                    self.weight_names.push(format!("{prefix}{idx}"));
+                }
+            }
+            WeightRepair::Leave => {}
+        }
+    }
+
+    /// Iterator over pairs of weight names and weights
+    ///
+    /// Names without a weight and weights without a name are skipped.
+    pub fn weight_map(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.weight_names
+            .iter()
+            .map(String::as_str)
+            .zip(self.weights.iter().copied())
+    }
+
+    /// Iterator over all particles in the event
+    ///
+    /// Each particle is visited exactly once. Particles are ordered
+    /// by vertex, and for each vertex incoming particles come before
+    /// outgoing ones.
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.vertices
+            .iter()
+            .flat_map(|v| v.particles_in.iter().chain(v.particles_out.iter()))
+    }
+
+    /// Mutable iterator over all particles in the event
+    ///
+    /// The ordering is the same as for [`particles`](Self::particles).
+    pub fn particles_mut(&mut self) -> impl Iterator<Item = &mut Particle> {
+        self.vertices.iter_mut().flat_map(|v| {
+            v.particles_in.iter_mut().chain(v.particles_out.iter_mut())
+        })
+    }
+
+    /// Iterator over all stable final-state particles
+    ///
+    /// Following the HepMC2 conventions, these are the particles with
+    /// status code 1.
+    pub fn final_state(&self) -> impl Iterator<Item = &Particle> {
+        self.particles().filter(|p| p.is_final())
+    }
+
+    /// Scalar sum of the transverse momenta of all final-state particles
+    pub fn scalar_ht(&self) -> f64 {
+        self.final_state().map(|p| p.p.pt()).sum()
+    }
+
+    /// Missing transverse momentum
+    ///
+    /// This is the negative vector sum of the transverse momenta of
+    /// all visible final-state particles. With the `pdg` feature,
+    /// neutrinos are considered invisible and excluded from the sum.
+    /// Without the `pdg` feature, all final-state particles are
+    /// considered visible. Other invisible particles, for example
+    /// from new physics models, are never excluded.
+    ///
+    /// The spatial components of the result are `(px, py, 0)` and
+    /// the time component is the magnitude of the missing
+    /// transverse momentum.
+    pub fn missing_et(&self) -> FourVector {
+        let visible: FourVector = self
+            .final_state()
+            .filter(|p| is_visible(p))
+            .map(|p| FourVector::txyz(0., p.p.px(), p.p.py(), 0.))
+            .sum();
+        FourVector::txyz(visible.pt(), -visible.px(), -visible.py(), 0.)
+    }
+
+    /// The two incoming beam particles
+    ///
+    /// Following the HepMC2 conventions, these are the first two
+    /// particles with status code 4. Returns `None` if there are
+    /// fewer than two such particles.
+    pub fn beam_particles(&self) -> Option<[&Particle; 2]> {
+        let mut beams = self.particles().filter(|p| p.is_beam());
+        Some([beams.next()?, beams.next()?])
+    }
+
+    /// Find the vertex with the given barcode
+    ///
+    /// This performs a linear search. For repeated lookups, consider
+    /// [`build_barcode_index`](Self::build_barcode_index).
+    pub fn vertex_by_barcode(&self, barcode: i32) -> Option<&Vertex> {
+        self.vertices.iter().find(|v| v.barcode == barcode)
+    }
+
+    /// The signal process vertex
+    ///
+    /// This is the vertex with the barcode
+    /// [signal_process_vertex](Self::signal_process_vertex). Returns
+    /// `None` if the barcode is 0 or there is no such vertex.
+    pub fn signal_process_vertex_ref(&self) -> Option<&Vertex> {
+        if self.signal_process_vertex == 0 {
+            return None;
+        }
+        self.vertex_by_barcode(self.signal_process_vertex)
+    }
+
+    /// Find the particle with the given barcode
+    ///
+    /// This performs a linear search. For repeated lookups, consider
+    /// [`build_barcode_index`](Self::build_barcode_index).
+    pub fn particle_by_barcode(&self, barcode: i32) -> Option<&Particle> {
+        self.particles().find(|p| p.barcode == barcode)
+    }
+
+    /// The particles produced in the decay of the given particle
+    ///
+    /// These are the outgoing particles of the end vertex of the
+    /// particle with barcode `particle_barcode`. The iterator is empty
+    /// if there is no such particle or it has no end vertex.
+    pub fn children(
+        &self,
+        particle_barcode: i32,
+    ) -> impl Iterator<Item = &Particle> {
+        self.particle_by_barcode(particle_barcode)
+            .filter(|p| p.end_vtx != 0)
+            .and_then(|p| self.vertex_by_barcode(p.end_vtx))
+            .into_iter()
+            .flat_map(|v| v.particles_out.iter())
+    }
+
+    /// All particles descending from the given particle
+    ///
+    /// The decay chain is traversed depth first, starting from the
+    /// [children](Self::children) of the particle with barcode
+    /// `particle_barcode`. Each vertex is visited at most once, so
+    /// this terminates even if the decay chain contains cycles.
+    pub fn descendants(
+        &self,
+        particle_barcode: i32,
+    ) -> impl Iterator<Item = &Particle> {
+        let index = self.build_barcode_index();
+        let mut visited = BTreeSet::new();
+        let mut stack = Vec::new();
+        let start = index
+            .particle(particle_barcode)
+            .filter(|p| p.end_vtx != 0)
+            .and_then(|p| index.vertex(p.end_vtx));
+        if let Some(vertex) = start {
+            visited.insert(vertex.barcode);
+            stack.push(vertex.particles_out.iter());
+        }
+        Descendants {
+            index,
+            visited,
+            stack,
+        }
+    }
+
+    /// Bring the stored angles of all particles into canonical ranges
+    ///
+    /// Different generators use different conventions for the range
+    /// of `phi`. This maps `phi` into (-π, π] and clamps `theta` into
+    /// [0, π] for each particle, see [Particle::normalize_angles].
+    /// Events are never normalised automatically.
+    pub fn normalize_angles(&mut self) {
+        for particle in self.particles_mut() {
+            particle.normalize_angles();
+        }
+    }
+
+    /// Set the stored angles of all particles from their momenta
+    ///
+    /// Generators do not always fill `theta` and `phi`, so this can be
+    /// used before writing files for tools that rely on them. See
+    /// [Particle::recompute_angles].
+    pub fn recompute_angles(&mut self) {
+        for particle in self.particles_mut() {
+            particle.recompute_angles();
+        }
+    }
+
+    /// Sort the vertices in canonical order
+    ///
+    /// Following the HepMC2 convention, vertices are ordered by
+    /// descending barcode, i.e. `-1, -2, -3, ...`. The relative order
+    /// of vertices with the same barcode is preserved. Since
+    /// [Writer](crate::Writer) writes the vertices in the order in which
+    /// they are stored, this can be used to make the output match
+    /// reference files.
+    pub fn sort_vertices(&mut self) {
+        self.vertices.sort_by_key(|v| std::cmp::Reverse(v.barcode));
+    }
+
+    /// Build an index for fast barcode lookups
+    pub fn build_barcode_index(&self) -> BarcodeIndex<'_> {
+        let vertices = self
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(pos, v)| (v.barcode, pos))
+            .collect();
+        let mut particles = BTreeMap::new();
+        for (vx_pos, vertex) in self.vertices.iter().enumerate() {
+            for (pos, p) in vertex.particles_in.iter().enumerate() {
+                particles.insert(p.barcode, (vx_pos, Direction::In, pos));
+            }
+            for (pos, p) in vertex.particles_out.iter().enumerate() {
+                particles.insert(p.barcode, (vx_pos, Direction::Out, pos));
+            }
+        }
+        BarcodeIndex {
+            event: self,
+            vertices,
+            particles,
+        }
+    }
+
+    /// Check that the event is self-consistent
+    ///
+    /// This checks that
+    /// - vertex barcodes are unique,
+    /// - the end vertex of each particle exists,
+    /// - momenta and the cross section are finite.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut barcodes = BTreeSet::new();
+        for vertex in &self.vertices {
+            if !barcodes.insert(vertex.barcode) {
+                return Err(ValidationError::DuplicateVertex(vertex.barcode));
+            }
+        }
+        for particle in self.particles() {
+            if particle.end_vtx != 0 && !barcodes.contains(&particle.end_vtx) {
+                return Err(ValidationError::DanglingEndVertex {
+                    particle: particle.barcode,
+                    end_vtx: particle.end_vtx,
+                });
+            }
+            if !particle.p.0.iter().all(|p| p.is_finite()) {
+                return Err(ValidationError::NonFiniteMomentum(
+                    particle.barcode,
+                ));
+            }
+        }
+        if !self.xs.cross_section.is_finite()
+            || !self.xs.cross_section_error.is_finite()
+        {
+            return Err(ValidationError::NonFiniteCrossSection);
+        }
+        Ok(())
+    }
+
+    /// Vertices violating four-momentum conservation
+    ///
+    /// Returns the barcode and the [momentum
+    /// imbalance](Vertex::momentum_imbalance) of each vertex where
+    /// any component of the imbalance exceeds `tol` in magnitude. In
+    /// contrast to [Vertex::momentum_imbalance], incoming particles
+    /// produced at other vertices are taken into account.
+    pub fn check_conservation(&self, tol: f64) -> Vec<(i32, FourVector)> {
+        self.imbalances(tol, true)
+    }
+
+    /// Vertices violating four-momentum conservation, excluding
+    /// sources
+    ///
+    /// Same as [check_conservation](Self::check_conservation), but
+    /// ignores source vertices, i.e. vertices without incoming
+    /// particles, e.g. for beam remnants.
+    pub fn check_conservation_excluding_sources(
+        &self,
+        tol: f64,
+    ) -> Vec<(i32, FourVector)> {
+        self.imbalances(tol, false)
+    }
+
+    /// Hash of the event contents
+    ///
+    /// The hash is stable between program runs and platforms and can
+    /// be used for deduplication. All floating-point numbers are
+    /// rounded to 40 mantissa bits, i.e. to a relative precision
+    /// of about 1e-12, before hashing. Thus, events that only differ
+    /// by the formatting of their numbers usually hash equal. Values
+    /// that differ by less than this precision can still hash
+    /// differently if they are rounded in different directions.
+    pub fn content_hash(&self) -> u64 {
+        let mut h = ContentHasher::new();
+        h.i32(self.number);
+        h.i32(self.mpi);
+        h.f64(self.scale);
+        h.f64(self.alpha_qcd);
+        h.f64(self.alpha_qed);
+        h.i32(self.signal_process_id);
+        h.i32(self.signal_process_vertex);
+        self.beam_barcodes.into_iter().for_each(|b| h.i32(b));
+        h.len(self.random_states.len());
+        self.random_states.iter().for_each(|&r| h.i64(r));
+        h.len(self.weights.len());
+        self.weights.iter().for_each(|&w| h.f64(w));
+        h.len(self.weight_names.len());
+        self.weight_names.iter().for_each(|name| h.str(name));
+        h.len(self.vertices.len());
+        for vertex in &self.vertices {
+            h.i32(vertex.barcode);
+            h.i32(vertex.status);
+            [vertex.x, vertex.y, vertex.z, vertex.t]
+                .into_iter()
+                .for_each(|x| h.f64(x));
+            h.len(vertex.weights.len());
+            vertex.weights.iter().for_each(|&w| h.f64(w));
+            for particles in [&vertex.particles_in, &vertex.particles_out] {
+                h.len(particles.len());
+                for particle in particles {
+                    h.i32(particle.barcode);
+                    h.i32(particle.id);
+                    particle.p.0.into_iter().for_each(|p| h.f64(p));
+                    h.f64(particle.m);
+                    h.i32(particle.status);
+                    h.f64(particle.theta);
+                    h.f64(particle.phi);
+                    h.len(particle.flows.len());
+                    for (&idx, &code) in &particle.flows {
+                        h.i32(idx);
+                        h.i32(code);
+                    }
+                    h.i32(particle.end_vtx);
+                }
+            }
+        }
+        h.f64(self.xs.cross_section);
+        h.f64(self.xs.cross_section_error);
+        let pdf = &self.pdf_info;
+        pdf.parton_id.into_iter().for_each(|id| h.i32(id));
+        pdf.x.into_iter().for_each(|x| h.f64(x));
+        h.f64(pdf.scale);
+        pdf.xf.into_iter().for_each(|xf| h.f64(xf));
+        pdf.pdf_id.into_iter().for_each(|id| h.i32(id));
+        h.u8(self.energy_unit as u8);
+        h.u8(self.length_unit as u8);
+        h.u8(self.units_declared as u8);
+        match &self.heavy_ion_info {
+            Some(hi) => {
+                h.u8(1);
+                [
+                    hi.ncoll_hard,
+                    hi.npart_proj,
+                    hi.npart_targ,
+                    hi.ncoll,
+                    hi.spectator_neutrons,
+                    hi.spectator_protons,
+                    hi.n_nwounded_collisions,
+                    hi.nwounded_n_collisions,
+                    hi.nwounded_nwounded_collisions,
+                ]
+                .into_iter()
+                .for_each(|n| h.i32(n));
+                [
+                    hi.impact_parameter,
+                    hi.event_plane_angle,
+                    hi.eccentricity,
+                    hi.sigma_inel_nn,
+                ]
+                .into_iter()
+                .for_each(|x| h.f64(x));
+            }
+            None => h.u8(0),
+        }
+        h.len(self.raw_extra.len());
+        self.raw_extra.iter().for_each(|line| h.str(line));
+        h.finish()
+    }
+
+    /// Compare events, allowing for small differences in floats
+    ///
+    /// All integers and strings have to agree exactly. Two floats `a`
+    /// and `b` are considered equal if `|a - b|` is at most `abs_tol`
+    /// or at most `rel_tol` times the larger of `|a|` and `|b|`. NaN
+    /// is never equal to anything.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::Event;
+    ///
+    /// let a = Event::new(1).with_weight(0.3);
+    /// let b = Event::new(1).with_weight(0.1 + 0.2);
+    /// assert_ne!(a, b);
+    /// assert!(a.approx_eq(&b, 1e-15, 0.));
+    /// ```
+    pub fn approx_eq(&self, other: &Event, rel_tol: f64, abs_tol: f64) -> bool {
+        self.approx_eq_with(other, rel_tol, abs_tol, false)
+    }
+
+    /// Compare events, ignoring the order of particles in each vertex
+    ///
+    /// This is the same as [approx_eq](Self::approx_eq), except that
+    /// the incoming and outgoing particles of each vertex are
+    /// compared as multisets. The order of the vertices still
+    /// matters.
+    pub fn eq_unordered(
+        &self,
+        other: &Event,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        self.approx_eq_with(other, rel_tol, abs_tol, true)
+    }
+
+    fn approx_eq_with(
+        &self,
+        other: &Event,
+        rel_tol: f64,
+        abs_tol: f64,
+        unordered: bool,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        let slice_eq = |a: &[f64], b: &[f64]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| eq(*a, *b))
+        };
+        let heavy_ion_eq = match (&self.heavy_ion_info, &other.heavy_ion_info) {
+            (Some(a), Some(b)) => a.approx_eq(b, rel_tol, abs_tol),
+            (None, None) => true,
+            _ => false,
+        };
+        self.number == other.number
+            && self.mpi == other.mpi
+            && eq(self.scale, other.scale)
+            && eq(self.alpha_qcd, other.alpha_qcd)
+            && eq(self.alpha_qed, other.alpha_qed)
+            && self.signal_process_id == other.signal_process_id
+            && self.signal_process_vertex == other.signal_process_vertex
+            && self.beam_barcodes == other.beam_barcodes
+            && self.random_states == other.random_states
+            && slice_eq(&self.weights, &other.weights)
+            && self.weight_names == other.weight_names
+            && self.vertices.len() == other.vertices.len()
+            && self
+                .vertices
+                .iter()
+                .zip(&other.vertices)
+                .all(|(a, b)| a.approx_eq_with(b, rel_tol, abs_tol, unordered))
+            && self.xs.approx_eq(&other.xs, rel_tol, abs_tol)
+            && self.pdf_info.approx_eq(&other.pdf_info, rel_tol, abs_tol)
+            && self.energy_unit == other.energy_unit
+            && self.length_unit == other.length_unit
+            && self.units_declared == other.units_declared
+            && heavy_ion_eq
+            && self.raw_extra == other.raw_extra
+    }
+
+    fn imbalances(
+        &self,
+        tol: f64,
+        include_sources: bool,
+    ) -> Vec<(i32, FourVector)> {
+        let mut incoming: BTreeMap<i32, FourVector> = BTreeMap::new();
+        for vertex in &self.vertices {
+            for particle in &vertex.particles_out {
+                if particle.end_vtx != 0 {
+                    *incoming.entry(particle.end_vtx).or_default() +=
+                        particle.p;
+                }
+            }
+        }
+        self.vertices
+            .iter()
+            .filter_map(|vertex| {
+                let produced = incoming.get(&vertex.barcode);
+                if !include_sources
+                    && produced.is_none()
+                    && vertex.particles_in.is_empty()
+                {
+                    return None;
+                }
+                let mut imbalance = vertex.momentum_imbalance();
+                if let Some(produced) = produced {
+                    imbalance -= *produced;
+                }
+                if imbalance.0.iter().any(|p| p.abs() > tol) {
+                    Some((vertex.barcode, imbalance))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Inconsistency found by [Event::validate]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    #[error("More than one vertex with barcode {0}")]
+    DuplicateVertex(i32),
+    #[error("End vertex {end_vtx} of particle {particle} does not exist")]
+    DanglingEndVertex { particle: i32, end_vtx: i32 },
+    #[error("Momentum of particle {0} is not finite")]
+    NonFiniteMomentum(i32),
+    #[error("Cross section is not finite")]
+    NonFiniteCrossSection,
+}
+
+/// How [Event::reconcile_weights] repairs mismatched weights and names
+///
+/// [PadNames](Self::PadNames) is the safest choice, since it never
+/// removes a weight.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum WeightRepair {
+    /// Remove weights without a name and names without a weight
+    TruncateToNames,
+    /// Name unnamed weights with the given prefix followed by their
+    /// index, and remove names without a weight
+    ///
+    /// With an empty prefix, this matches the default HepMC2 weight
+    /// names.
+    PadNames(String),
+    /// Keep weights and names as they are
+    #[default]
+    Leave,
+}
+
+/// Index for looking up event entries by their barcode
+#[derive(Debug, Clone)]
+pub struct BarcodeIndex<'a> {
+    event: &'a Event,
+    vertices: BTreeMap<i32, usize>,
+    particles: BTreeMap<i32, (usize, Direction, usize)>,
+}
+
+#[cfg(feature = "pdg")]
+fn is_visible(particle: &Particle) -> bool {
+    !particle.is_neutrino()
+}
+
+#[cfg(not(feature = "pdg"))]
+fn is_visible(_particle: &Particle) -> bool {
+    true
+}
+
+fn set_flow(flows: &mut BTreeMap<i32, i32>, idx: i32, code: Option<i32>) {
+    match code {
+        Some(code) => flows.insert(idx, code),
+        None => flows.remove(&idx),
+    };
+}
+
+/// 64-bit FNV-1a hasher used by [Event::content_hash]
+///
+/// In contrast to [std::hash::Hasher] implementations, the input is
+/// fed in a fixed byte order independent of the platform.
+struct ContentHasher(u64);
+
+impl ContentHasher {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.bytes(&[n])
+    }
+
+    fn i32(&mut self, n: i32) {
+        self.bytes(&n.to_le_bytes())
+    }
+
+    fn i64(&mut self, n: i64) {
+        self.bytes(&n.to_le_bytes())
+    }
+
+    fn len(&mut self, len: usize) {
+        self.bytes(&(len as u64).to_le_bytes())
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.bytes(s.as_bytes())
+    }
+
+    /// Hash a float with the mantissa rounded to 40 bits
+    fn f64(&mut self, x: f64) {
+        // map -0 to +0 and all NaNs to the same value
+        let x = if x.is_nan() { f64::NAN } else { x + 0. };
+        const DROPPED_BITS: u32 = 52 - 40;
+        let bits = x.to_bits();
+        let rounded =
+            bits.wrapping_add(1 << (DROPPED_BITS - 1)) >> DROPPED_BITS;
+        self.bytes(&rounded.to_le_bytes())
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Whether two floats agree within the given tolerances
+///
+/// Infinities only agree with themselves and NaN never agrees.
+fn approx_eq(a: f64, b: f64, rel_tol: f64, abs_tol: f64) -> bool {
+    if a == b {
+        return true;
+    }
+    if !a.is_finite() || !b.is_finite() {
+        return false;
+    }
+    let diff = (a - b).abs();
+    diff <= abs_tol || diff <= rel_tol * a.abs().max(b.abs())
+}
+
+/// Compare particles as multisets, allowing for small differences
+/// in floats
+///
+/// Each particle in `a` is matched to the first unmatched particle in
+/// `b` that agrees within the tolerances. To speed up the search, the
+/// particles in `b` are sorted by their integer fields, which have to
+/// agree exactly.
+fn particles_eq_unordered(
+    a: &[Particle],
+    b: &[Particle],
+    rel_tol: f64,
+    abs_tol: f64,
+) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let key = |p: &Particle| (p.barcode, p.id, p.status, p.end_vtx);
+    let mut b: Vec<_> = b.iter().collect();
+    b.sort_by_key(|p| key(p));
+    let mut matched = vec![false; b.len()];
+    a.iter().all(|pa| {
+        let start = b.partition_point(|pb| key(pb) < key(pa));
+        let end = b.partition_point(|pb| key(pb) <= key(pa));
+        let found = (start..end).find(|&idx| {
+            !matched[idx] && pa.approx_eq(b[idx], rel_tol, abs_tol)
+        });
+        if let Some(idx) = found {
+            matched[idx] = true;
+        }
+        found.is_some()
+    })
+}
+
+/// Map an azimuthal angle into (-π, π]
+fn wrap_phi(phi: f64) -> f64 {
+    use std::f64::consts::PI;
+    let phi = phi % (2. * PI);
+    if phi > PI {
+        phi - 2. * PI
+    } else if phi <= -PI {
+        phi + 2. * PI
+    } else {
+        phi
+    }
+}
+
+/// Depth-first iterator over the descendants of a particle
+struct Descendants<'a> {
+    index: BarcodeIndex<'a>,
+    visited: BTreeSet<i32>,
+    stack: Vec<std::slice::Iter<'a, Particle>>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Particle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(particle) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            if particle.end_vtx != 0 && self.visited.insert(particle.end_vtx) {
+                if let Some(vertex) = self.index.vertex(particle.end_vtx) {
+                    self.stack.push(vertex.particles_out.iter());
+                }
+            }
+            return Some(particle);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Direction {
+    In,
+    Out,
+}
+
+impl<'a> BarcodeIndex<'a> {
+    /// Find the vertex with the given barcode
+    pub fn vertex(&self, barcode: i32) -> Option<&'a Vertex> {
+        let pos = *self.vertices.get(&barcode)?;
+        Some(&self.event.vertices[pos])
+    }
+
+    /// Find the particle with the given barcode
+    pub fn particle(&self, barcode: i32) -> Option<&'a Particle> {
+        let (vx_pos, dir, pos) = *self.particles.get(&barcode)?;
+        let vertex = &self.event.vertices[vx_pos];
+        let particles = match dir {
+            Direction::In => &vertex.particles_in,
+            Direction::Out => &vertex.particles_out,
+        };
+        Some(&particles[pos])
+    }
 }
 
 /// Interaction vertex
-#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vertex {
     pub barcode: i32,
     pub status: i32,
@@ -34,13 +900,90 @@ pub struct Vertex {
     pub z: f64,
     pub t: f64,
     pub weights: Vec<f64>,
+    /// Orphan incoming particles, i.e. those without a production vertex
+    ///
+    /// Incoming particles that are produced at another vertex are
+    /// only stored in the `particles_out` of that vertex.
     pub particles_in: Vec<Particle>,
     pub particles_out: Vec<Particle>,
 }
 
+impl Vertex {
+    /// Construct a vertex without particles
+    ///
+    /// The position and weights take their default values.
+    pub fn new(barcode: i32, status: i32) -> Self {
+        Self {
+            barcode,
+            status,
+            ..Default::default()
+        }
+    }
+
+    /// Sum of outgoing minus sum of incoming particle momenta
+    ///
+    /// Only the incoming particles stored in this vertex are
+    /// considered, i.e. those without a production vertex. For
+    /// the full balance use [Event::check_conservation].
+    pub fn momentum_imbalance(&self) -> FourVector {
+        let outgoing: FourVector =
+            self.particles_out.iter().map(|p| &p.p).sum();
+        let incoming: FourVector = self.particles_in.iter().map(|p| &p.p).sum();
+        outgoing - incoming
+    }
+
+    /// Compare vertices, allowing for small differences in floats
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &Vertex,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        self.approx_eq_with(other, rel_tol, abs_tol, false)
+    }
+
+    fn approx_eq_with(
+        &self,
+        other: &Vertex,
+        rel_tol: f64,
+        abs_tol: f64,
+        unordered: bool,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        let particles_eq = |a: &[Particle], b: &[Particle]| {
+            if unordered {
+                particles_eq_unordered(a, b, rel_tol, abs_tol)
+            } else {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(a, b)| a.approx_eq(b, rel_tol, abs_tol))
+            }
+        };
+        self.barcode == other.barcode
+            && self.status == other.status
+            && eq(self.x, other.x)
+            && eq(self.y, other.y)
+            && eq(self.z, other.z)
+            && eq(self.t, other.t)
+            && self.weights.len() == other.weights.len()
+            && self
+                .weights
+                .iter()
+                .zip(&other.weights)
+                .all(|(a, b)| eq(*a, *b))
+            && particles_eq(&self.particles_in, &other.particles_in)
+            && particles_eq(&self.particles_out, &other.particles_out)
+    }
+}
+
 /// Particle
-#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Particle {
+    pub barcode: i32,
     pub id: i32,
     pub p: FourVector,
     pub m: f64,
@@ -51,10 +994,176 @@ pub struct Particle {
     pub end_vtx: i32,
 }
 
+impl Particle {
+    /// Construct a particle with the given PDG id, momentum, and status
+    ///
+    /// All other fields take their default values. In particular, the
+    /// barcode is zero and there is no end vertex.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::{FourVector, Particle};
+    ///
+    /// let p = FourVector::txyz(10., 0., 0., 10.);
+    /// let photon = Particle::new(22, p, 1);
+    /// assert!(photon.is_final());
+    /// ```
+    pub fn new(id: i32, p: FourVector, status: i32) -> Self {
+        Self {
+            id,
+            p,
+            status,
+            ..Default::default()
+        }
+    }
+
+    /// Classify the status code
+    pub fn status_class(&self) -> StatusClass {
+        StatusClass::from(self.status)
+    }
+
+    /// Whether this is a stable final-state particle (status 1)
+    pub fn is_final(&self) -> bool {
+        self.status_class() == StatusClass::Final
+    }
+
+    /// Whether this is a decayed or fragmented particle (status 2)
+    pub fn is_decayed(&self) -> bool {
+        self.status_class() == StatusClass::Decayed
+    }
+
+    /// Whether this is an incoming beam particle (status 4)
+    pub fn is_beam(&self) -> bool {
+        self.status_class() == StatusClass::Beam
+    }
+
+    /// Difference in pseudorapidity
+    pub fn delta_eta(&self, other: &Particle) -> f64 {
+        self.p.eta() - other.p.eta()
+    }
+
+    /// Difference in azimuthal angle, wrapped into (-π, π]
+    pub fn delta_phi(&self, other: &Particle) -> f64 {
+        wrap_phi(self.p.phi() - other.p.phi())
+    }
+
+    /// Map `phi` into (-π, π] and clamp `theta` into [0, π]
+    ///
+    /// See also [Event::normalize_angles].
+    pub fn normalize_angles(&mut self) {
+        self.phi = wrap_phi(self.phi);
+        self.theta = self.theta.clamp(0., std::f64::consts::PI);
+    }
+
+    /// The colour flow code, i.e. the flow with index 1
+    pub fn color(&self) -> Option<i32> {
+        self.flows.get(&1).copied()
+    }
+
+    /// The anticolour flow code, i.e. the flow with index 2
+    pub fn anticolor(&self) -> Option<i32> {
+        self.flows.get(&2).copied()
+    }
+
+    /// Set or remove the colour flow code
+    ///
+    /// Flows with indices other than 1 are left untouched.
+    pub fn set_color(&mut self, color: Option<i32>) {
+        set_flow(&mut self.flows, 1, color)
+    }
+
+    /// Set or remove the anticolour flow code
+    ///
+    /// Flows with indices other than 2 are left untouched.
+    pub fn set_anticolor(&mut self, anticolor: Option<i32>) {
+        set_flow(&mut self.flows, 2, anticolor)
+    }
+
+    /// Set `theta` and `phi` from the momentum
+    ///
+    /// The angles are set to zero if the spatial momentum vanishes.
+    /// See also [Event::recompute_angles].
+    pub fn recompute_angles(&mut self) {
+        self.theta = self.p.theta();
+        self.phi = self.p.phi();
+    }
+
+    /// Angular separation ΔR = sqrt(Δη^2 + Δφ^2)
+    pub fn delta_r(&self, other: &Particle) -> f64 {
+        self.delta_eta(other).hypot(self.delta_phi(other))
+    }
+
+    /// Compare particles, allowing for small differences in floats
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &Particle,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        self.barcode == other.barcode
+            && self.id == other.id
+            && self.p.approx_eq(&other.p, rel_tol, abs_tol)
+            && eq(self.m, other.m)
+            && self.status == other.status
+            && eq(self.theta, other.theta)
+            && eq(self.phi, other.phi)
+            && self.flows == other.flows
+            && self.end_vtx == other.end_vtx
+    }
+}
+
+/// Classification of particle status codes
+///
+/// The HepMC2 conventions are
+///
+/// | Status  | Meaning                                         |
+/// |---------|-------------------------------------------------|
+/// | 0       | no meaning, should not occur                    |
+/// | 1       | undecayed physical particle in the final state  |
+/// | 2       | decayed or fragmented particle                  |
+/// | 3       | documentation line, e.g. in the hard process    |
+/// | 4       | incoming beam particle                          |
+/// | 11-200  | reserved for use by event generators            |
+/// | 201-    | free for use by users                           |
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusClass {
+    /// Status 0
+    Null,
+    /// Status 1
+    Final,
+    /// Status 2
+    Decayed,
+    /// Status 3
+    Documentation,
+    /// Status 4
+    Beam,
+    /// Any other status code, e.g. generator-specific ones
+    Other(i32),
+}
+
+impl From<i32> for StatusClass {
+    fn from(status: i32) -> Self {
+        match status {
+            0 => Self::Null,
+            1 => Self::Final,
+            2 => Self::Decayed,
+            3 => Self::Documentation,
+            4 => Self::Beam,
+            other => Self::Other(other),
+        }
+    }
+}
+
 /// Simple Lorentz vector with components (t, x, y, z)
-#[derive(
-    Debug, PartialEq, PartialOrd, Default, Copy, Clone, Serialize, Deserialize,
-)]
+///
+/// For momenta, the components are (E, px, py, pz) and the z axis
+/// is the beam axis.
+#[derive(Debug, PartialEq, PartialOrd, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FourVector(pub [f64; 4]);
 
 impl FourVector {
@@ -65,8 +1174,226 @@ impl FourVector {
     pub fn txyz(t: f64, x: f64, y: f64, z: f64) -> Self {
         FourVector([t, x, y, z])
     }
+
+    /// Energy, i.e. the time component
+    pub fn e(&self) -> f64 {
+        self.0[0]
+    }
+
+    /// Momentum in x direction
+    pub fn px(&self) -> f64 {
+        self.0[1]
+    }
+
+    /// Momentum in y direction
+    pub fn py(&self) -> f64 {
+        self.0[2]
+    }
+
+    /// Momentum in z direction, i.e. along the beam axis
+    pub fn pz(&self) -> f64 {
+        self.0[3]
+    }
+
+    /// Squared transverse momentum
+    pub fn pt2(&self) -> f64 {
+        self.px() * self.px() + self.py() * self.py()
+    }
+
+    /// Transverse momentum
+    pub fn pt(&self) -> f64 {
+        self.pt2().sqrt()
+    }
+
+    /// Absolute value of the spatial momentum
+    pub fn p3_abs(&self) -> f64 {
+        (self.pt2() + self.pz() * self.pz()).sqrt()
+    }
+
+    /// Squared invariant mass, t^2 - x^2 - y^2 - z^2
+    pub fn m2(&self) -> f64 {
+        self.e() * self.e() - self.pt2() - self.pz() * self.pz()
+    }
+
+    /// Invariant mass
+    ///
+    /// For spacelike vectors this is the negative square root of
+    /// -[`m2`](Self::m2).
+    pub fn m(&self) -> f64 {
+        let m2 = self.m2();
+        if m2 >= 0. {
+            m2.sqrt()
+        } else {
+            -(-m2).sqrt()
+        }
+    }
+
+    /// Azimuthal angle in (-π, π]
+    ///
+    /// Returns 0 if the transverse momentum vanishes.
+    pub fn phi(&self) -> f64 {
+        if self.pt2() == 0. {
+            0.
+        } else {
+            self.py().atan2(self.px())
+        }
+    }
+
+    /// Polar angle with respect to the beam axis in [0, π]
+    ///
+    /// Returns 0 if the spatial momentum vanishes.
+    pub fn theta(&self) -> f64 {
+        if self.pt2() == 0. && self.pz() == 0. {
+            0.
+        } else {
+            self.pt().atan2(self.pz())
+        }
+    }
+
+    /// Pseudorapidity
+    ///
+    /// Returns 0 for vanishing spatial momentum and ±∞ for momenta
+    /// along the beam axis.
+    pub fn eta(&self) -> f64 {
+        let pt = self.pt();
+        if pt == 0. {
+            if self.pz() == 0. {
+                0.
+            } else {
+                f64::INFINITY.copysign(self.pz())
+            }
+        } else {
+            (self.pz() / pt).asinh()
+        }
+    }
+
+    /// Rapidity, (ln((E + pz) / (E - pz))) / 2
+    pub fn rapidity(&self) -> f64 {
+        0.5 * ((self.e() + self.pz()) / (self.e() - self.pz())).ln()
+    }
+
+    /// Minkowski product with metric (+, -, -, -)
+    pub fn dot(&self, other: &FourVector) -> f64 {
+        self[0] * other[0]
+            - self[1] * other[1]
+            - self[2] * other[2]
+            - self[3] * other[3]
+    }
+
+    /// Spatial velocity (x/t, y/t, z/t)
+    pub fn beta(&self) -> [f64; 3] {
+        let [t, x, y, z] = self.0;
+        [x / t, y / t, z / t]
+    }
+
+    /// Lorentz boost with velocity `beta`
+    ///
+    /// A vector at rest acquires the velocity `beta` after the
+    /// boost. Fails if `beta` is not strictly below the speed of light.
+    pub fn boost(&self, beta: [f64; 3]) -> Result<FourVector, BoostError> {
+        let b2: f64 = beta.iter().map(|b| b * b).sum();
+        if b2 >= 1. || b2.is_nan() {
+            return Err(BoostError(b2.sqrt()));
+        }
+        let gamma = 1. / (1. - b2).sqrt();
+        let [t, x, y, z] = self.0;
+        let bp = beta[0] * x + beta[1] * y + beta[2] * z;
+        let gamma2 = if b2 > 0. { (gamma - 1.) / b2 } else { 0. };
+        let coeff = gamma2 * bp + gamma * t;
+        Ok(FourVector([
+            gamma * (t + bp),
+            x + coeff * beta[0],
+            y + coeff * beta[1],
+            z + coeff * beta[2],
+        ]))
+    }
+
+    /// Lorentz boost into the rest frame of `frame`
+    pub fn boost_to_rest_frame(
+        &self,
+        frame: &FourVector,
+    ) -> Result<FourVector, BoostError> {
+        let [bx, by, bz] = frame.beta();
+        self.boost([-bx, -by, -bz])
+    }
+
+    /// Compare components, allowing for small differences
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &FourVector,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        self.0
+            .iter()
+            .zip(other.0)
+            .all(|(a, b)| approx_eq(*a, b, rel_tol, abs_tol))
+    }
+}
+
+impl std::ops::Add for FourVector {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut res = self;
+        res += rhs;
+        res
+    }
 }
 
+impl std::ops::AddAssign for FourVector {
+    fn add_assign(&mut self, rhs: Self) {
+        for (lhs, rhs) in self.0.iter_mut().zip(rhs.0) {
+            *lhs += rhs;
+        }
+    }
+}
+
+impl std::ops::Sub for FourVector {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut res = self;
+        res -= rhs;
+        res
+    }
+}
+
+impl std::ops::SubAssign for FourVector {
+    fn sub_assign(&mut self, rhs: Self) {
+        for (lhs, rhs) in self.0.iter_mut().zip(rhs.0) {
+            *lhs -= rhs;
+        }
+    }
+}
+
+impl std::ops::Mul<f64> for FourVector {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        FourVector(self.0.map(|c| c * rhs))
+    }
+}
+
+impl std::iter::Sum for FourVector {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |acc, p| acc + p)
+    }
+}
+
+impl<'a> std::iter::Sum<&'a FourVector> for FourVector {
+    fn sum<I: Iterator<Item = &'a FourVector>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+/// Error for Lorentz boosts with velocity |β| ≥ 1
+#[derive(Debug, Copy, Clone, PartialEq, Error)]
+#[error("Boost velocity |β| = {0} is not below the speed of light")]
+pub struct BoostError(pub f64);
+
 impl std::ops::Index<usize> for FourVector {
     type Output = f64;
 
@@ -81,10 +1408,91 @@ impl std::ops::IndexMut<usize> for FourVector {
     }
 }
 
+/// Names of the event weights
+///
+/// The names can be shared between events, see
+/// [set_intern_weight_names](crate::Reader::set_intern_weight_names).
+/// Shared names are copied before they are modified.
+///
+/// # Example
+///
+/// ```
+/// use hepmc2::event::WeightNames;
+///
+/// let names = WeightNames::from(vec!["a".to_owned()]);
+/// let mut copy = names.clone();
+/// assert!(copy.ptr_eq(&names));
+/// copy.push("b".to_owned());
+/// assert!(!copy.ptr_eq(&names));
+/// assert_eq!(names, ["a"]);
+/// assert_eq!(copy, ["a", "b"]);
+/// ```
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct WeightNames(Arc<Vec<String>>);
+
+impl WeightNames {
+    /// Construct empty `WeightNames`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether both share the same allocation
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Convert into a vector, copying the names only if they are shared
+    pub fn into_vec(self) -> Vec<String> {
+        Arc::unwrap_or_clone(self.0)
+    }
+}
+
+impl std::ops::Deref for WeightNames {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for WeightNames {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl From<Vec<String>> for WeightNames {
+    fn from(names: Vec<String>) -> Self {
+        Self(Arc::new(names))
+    }
+}
+
+impl FromIterator<String> for WeightNames {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<'a> IntoIterator for &'a WeightNames {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<const N: usize> PartialEq<[&str; N]> for WeightNames {
+    fn eq(&self, other: &[&str; N]) -> bool {
+        *self.0 == other
+    }
+}
+
 /// Cross section with error
-#[derive(
-    Debug, PartialEq, PartialOrd, Default, Copy, Clone, Serialize, Deserialize,
-)]
+#[derive(Debug, PartialEq, PartialOrd, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CrossSection {
     pub cross_section: f64,
     pub cross_section_error: f64,
@@ -96,10 +1504,78 @@ impl std::fmt::Display for CrossSection {
     }
 }
 
+impl CrossSection {
+    /// The error relative to the cross section
+    ///
+    /// Returns zero if both the cross section and its error vanish,
+    /// and infinity if only the cross section vanishes.
+    pub fn relative_error(&self) -> f64 {
+        if self.cross_section_error == 0. {
+            return 0.;
+        }
+        self.cross_section_error / self.cross_section.abs()
+    }
+
+    /// Combine independent measurements of the same cross section
+    ///
+    /// The result is the weighted mean with weights given by the
+    /// inverse squared errors. A cross section with zero error is
+    /// exact and takes precedence: if only one of the errors
+    /// vanishes, that cross section is returned, and if both vanish,
+    /// the result is the plain mean with zero error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::CrossSection;
+    ///
+    /// let a = CrossSection {
+    ///     cross_section: 1.,
+    ///     cross_section_error: 0.1,
+    /// };
+    /// let combined = a.combine(&a);
+    /// assert_eq!(combined.cross_section, 1.);
+    /// ```
+    pub fn combine(&self, other: &CrossSection) -> CrossSection {
+        match (self.cross_section_error, other.cross_section_error) {
+            (0., 0.) => CrossSection {
+                cross_section: (self.cross_section + other.cross_section) / 2.,
+                cross_section_error: 0.,
+            },
+            (0., _) => *self,
+            (_, 0.) => *other,
+            (err1, err2) => {
+                let w1 = err1.powi(-2);
+                let w2 = err2.powi(-2);
+                let norm = w1 + w2;
+                CrossSection {
+                    cross_section: (w1 * self.cross_section
+                        + w2 * other.cross_section)
+                        / norm,
+                    cross_section_error: norm.sqrt().recip(),
+                }
+            }
+        }
+    }
+
+    /// Compare cross sections, allowing for small differences
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &CrossSection,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        eq(self.cross_section, other.cross_section)
+            && eq(self.cross_section_error, other.cross_section_error)
+    }
+}
+
 /// PDF information
-#[derive(
-    Debug, PartialEq, PartialOrd, Default, Copy, Clone, Serialize, Deserialize,
-)]
+#[derive(Debug, PartialEq, PartialOrd, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PdfInfo {
     pub parton_id: [i32; 2],
     pub x: [f64; 2],
@@ -108,10 +1584,31 @@ pub struct PdfInfo {
     pub pdf_id: [i32; 2],
 }
 
+impl PdfInfo {
+    /// Compare PDF information, allowing for small differences in
+    /// floats
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &PdfInfo,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        self.parton_id == other.parton_id
+            && eq(self.x[0], other.x[0])
+            && eq(self.x[1], other.x[1])
+            && eq(self.scale, other.scale)
+            && eq(self.xf[0], other.xf[0])
+            && eq(self.xf[1], other.xf[1])
+            && self.pdf_id == other.pdf_id
+    }
+}
+
 /// Information for heavy ion collisions
-#[derive(
-    Debug, PartialEq, PartialOrd, Default, Copy, Clone, Serialize, Deserialize,
-)]
+#[derive(Debug, PartialEq, PartialOrd, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HeavyIonInfo {
     pub ncoll_hard: i32,
     pub npart_proj: i32,
@@ -128,6 +1625,35 @@ pub struct HeavyIonInfo {
     pub sigma_inel_nn: f64,
 }
 
+impl HeavyIonInfo {
+    /// Compare heavy ion information, allowing for small differences
+    /// in floats
+    ///
+    /// See [Event::approx_eq].
+    pub fn approx_eq(
+        &self,
+        other: &HeavyIonInfo,
+        rel_tol: f64,
+        abs_tol: f64,
+    ) -> bool {
+        let eq = |a, b| approx_eq(a, b, rel_tol, abs_tol);
+        self.ncoll_hard == other.ncoll_hard
+            && self.npart_proj == other.npart_proj
+            && self.npart_targ == other.npart_targ
+            && self.ncoll == other.ncoll
+            && self.spectator_neutrons == other.spectator_neutrons
+            && self.spectator_protons == other.spectator_protons
+            && self.n_nwounded_collisions == other.n_nwounded_collisions
+            && self.nwounded_n_collisions == other.nwounded_n_collisions
+            && self.nwounded_nwounded_collisions
+                == other.nwounded_nwounded_collisions
+            && eq(self.impact_parameter, other.impact_parameter)
+            && eq(self.event_plane_angle, other.event_plane_angle)
+            && eq(self.eccentricity, other.eccentricity)
+            && eq(self.sigma_inel_nn, other.sigma_inel_nn)
+    }
+}
+
 /// Energy units
 #[derive(
     EnumString,
@@ -139,17 +1665,49 @@ pub struct HeavyIonInfo {
     Hash,
     Copy,
     Clone,
-    Serialize,
-    Deserialize,
+    Default,
 )]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[strum(ascii_case_insensitive)]
 pub enum EnergyUnit {
+    #[strum(serialize = "MEV")]
     MEV,
+    #[default]
+    #[strum(serialize = "GEV")]
     GEV,
 }
 
-impl std::default::Default for EnergyUnit {
-    fn default() -> Self {
-        Self::GEV
+impl std::fmt::Display for EnergyUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self {
+            Self::MEV => "MEV",
+            Self::GEV => "GEV",
+        };
+        f.write_str(unit)
+    }
+}
+
+impl EnergyUnit {
+    /// The value of this unit in MeV
+    pub fn to_mev_factor(&self) -> f64 {
+        match self {
+            Self::MEV => 1.,
+            Self::GEV => 1e3,
+        }
+    }
+
+    /// Factor for converting energies from `from` to `to` units
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::EnergyUnit::{self, GEV, MEV};
+    ///
+    /// let factor = EnergyUnit::conversion_factor(GEV, MEV);
+    /// assert_eq!(2. * factor, 2000.);
+    /// ```
+    pub fn conversion_factor(from: Self, to: Self) -> f64 {
+        from.to_mev_factor() / to.to_mev_factor()
     }
 }
 
@@ -164,16 +1722,627 @@ impl std::default::Default for EnergyUnit {
     Hash,
     Copy,
     Clone,
-    Serialize,
-    Deserialize,
+    Default,
 )]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[strum(ascii_case_insensitive)]
 pub enum LengthUnit {
+    #[strum(serialize = "MM")]
     MM,
+    #[default]
+    #[strum(serialize = "CM")]
     CM,
 }
 
-impl std::default::Default for LengthUnit {
-    fn default() -> Self {
-        Self::CM
+impl std::fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self {
+            Self::MM => "MM",
+            Self::CM => "CM",
+        };
+        f.write_str(unit)
+    }
+}
+
+impl LengthUnit {
+    /// The value of this unit in mm
+    pub fn to_mm_factor(&self) -> f64 {
+        match self {
+            Self::MM => 1.,
+            Self::CM => 10.,
+        }
+    }
+
+    /// Factor for converting lengths from `from` to `to` units
+    pub fn conversion_factor(from: Self, to: Self) -> f64 {
+        from.to_mm_factor() / to.to_mm_factor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f64 = 1e-10;
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        assert!(
+            (a - b).abs() < EPS * (1. + a.abs().max(b.abs())),
+            "{a} != {b}"
+        );
+    }
+
+    #[test]
+    fn tst_approx_eq() {
+        let event = Event::new(0).with_weight(1.);
+        let close = Event::new(0).with_weight(1. + 1e-12);
+        assert!(event.approx_eq(&close, 1e-10, 0.));
+        assert!(event.approx_eq(&close, 0., 1e-10));
+        assert!(!event.approx_eq(&close, 1e-14, 1e-14));
+        let longer = close.clone().with_weight(1.);
+        assert!(!event.approx_eq(&longer, 1., 1.));
+
+        let nan = Event::new(0).with_weight(f64::NAN);
+        assert!(!nan.approx_eq(&nan, 1., 1.));
+        let inf = Event::new(0).with_weight(f64::INFINITY);
+        assert!(inf.approx_eq(&inf, 0., 0.));
+        assert!(!inf.approx_eq(&event, 1., 1.));
+    }
+
+    #[test]
+    fn tst_combine_cross_sections() {
+        let a = CrossSection {
+            cross_section: 10.,
+            cross_section_error: 1.,
+        };
+        let b = CrossSection {
+            cross_section: 12.,
+            cross_section_error: 2.,
+        };
+        // weights 1 and 1/4
+        let combined = a.combine(&b);
+        assert_approx_eq(combined.cross_section, (10. + 12. / 4.) / 1.25);
+        assert_approx_eq(combined.cross_section_error, 1.25f64.sqrt().recip());
+        assert_eq!(a.combine(&b), b.combine(&a));
+        assert_approx_eq(a.relative_error(), 0.1);
+
+        let exact = CrossSection {
+            cross_section: 11.,
+            cross_section_error: 0.,
+        };
+        assert_eq!(a.combine(&exact), exact);
+        assert_eq!(exact.combine(&a), exact);
+        let zero = CrossSection::default();
+        assert_eq!(zero.relative_error(), 0.);
+        assert_eq!(exact.combine(&zero).cross_section, 5.5);
+        let no_xs = CrossSection {
+            cross_section: 0.,
+            cross_section_error: 1.,
+        };
+        assert_eq!(no_xs.relative_error(), f64::INFINITY);
+    }
+
+    #[test]
+    fn tst_boost_rest_frame() {
+        let p = FourVector::txyz(5., 0., 0., 4.);
+        let rest = p.boost_to_rest_frame(&p).unwrap();
+        assert_approx_eq(rest[0], 3.);
+        for i in 1..4 {
+            assert_approx_eq(rest[i], 0.);
+        }
+
+        let p = FourVector::txyz(5., 1., -2., 3.);
+        let rest = p.boost_to_rest_frame(&p).unwrap();
+        assert_approx_eq(rest[0], 11f64.sqrt());
+        for i in 1..4 {
+            assert_approx_eq(rest[i], 0.);
+        }
+        let back = rest.boost(p.beta()).unwrap();
+        for i in 0..4 {
+            assert_approx_eq(back[i], p[i]);
+        }
+    }
+
+    #[test]
+    fn tst_kinematics() {
+        let p = FourVector::txyz(13., 3., 4., 12.);
+        assert_eq!(p.e(), 13.);
+        assert_eq!(p.px(), 3.);
+        assert_eq!(p.py(), 4.);
+        assert_eq!(p.pz(), 12.);
+        assert_eq!(p.pt2(), 25.);
+        assert_eq!(p.pt(), 5.);
+        assert_eq!(p.p3_abs(), 13.);
+        assert_eq!(p.m(), 0.);
+        assert_approx_eq(p.phi(), 4f64.atan2(3.));
+        assert_approx_eq(p.eta(), 0.5 * (25f64 / 1.).ln());
+        assert_approx_eq(p.rapidity(), p.eta());
+
+        let p = FourVector::txyz(5., 0., 0., 4.);
+        assert_eq!(p.m(), 3.);
+        assert_eq!(p.phi(), 0.);
+        assert_eq!(p.eta(), f64::INFINITY);
+        assert_eq!(FourVector::txyz(5., 0., 0., -4.).eta(), f64::NEG_INFINITY);
+        assert_approx_eq(p.rapidity(), 0.5 * 9f64.ln());
+
+        let p = FourVector::txyz(1., 0., -2., 0.);
+        assert_eq!(p.m(), -3f64.sqrt());
+        assert_eq!(p.eta(), 0.);
+        assert_approx_eq(p.phi(), -std::f64::consts::FRAC_PI_2);
+
+        let p = FourVector::txyz(1., -1., 0., 0.);
+        assert_approx_eq(p.phi(), std::f64::consts::PI);
+        assert_eq!(FourVector::new().eta(), 0.);
+        assert_eq!(FourVector::new().phi(), 0.);
+    }
+
+    #[test]
+    fn tst_arithmetic() {
+        let p = FourVector::txyz(5., 1., -2., 3.);
+        let q = FourVector::txyz(2., 0.5, 1., -1.);
+        assert_eq!(p.dot(&p), p.m2());
+        assert_eq!(p.dot(&q), q.dot(&p));
+        assert_eq!((p + p).dot(&(p + p)), 4. * p.dot(&p));
+        assert_eq!(p + p, p * 2.);
+        assert_eq!(p + q - q, p);
+        assert_eq!(p - p, FourVector::new());
+        assert_eq!([p, q].into_iter().sum::<FourVector>(), p + q);
+        assert_eq!([p, q, p].iter().sum::<FourVector>(), p * 2. + q);
+        assert_eq!(
+            std::iter::empty::<FourVector>().sum::<FourVector>(),
+            FourVector::new()
+        );
+    }
+
+    #[test]
+    fn tst_angular_separation() {
+        use std::f64::consts::PI;
+
+        let particle = |phi: f64, eta: f64| Particle {
+            p: FourVector::txyz(eta.cosh(), phi.cos(), phi.sin(), eta.sinh()),
+            ..Default::default()
+        };
+
+        let p1 = particle(0.1, 0.5);
+        let p2 = particle(0.4, -1.5);
+        assert_approx_eq(p1.delta_phi(&p2), -0.3);
+        assert_approx_eq(p2.delta_phi(&p1), 0.3);
+        assert_approx_eq(p1.delta_eta(&p2), 2.);
+        assert_approx_eq(p1.delta_r(&p2), (4f64 + 0.09).sqrt());
+
+        // wrap-around at φ = ±π
+        let p1 = particle(PI - 0.1, 0.);
+        let p2 = particle(-PI + 0.1, 0.);
+        assert_approx_eq(p1.delta_phi(&p2), -0.2);
+        assert_approx_eq(p2.delta_phi(&p1), 0.2);
+        assert_approx_eq(p1.delta_r(&p2), 0.2);
+
+        // back-to-back
+        let p1 = particle(PI / 2., 0.);
+        let p2 = particle(-PI / 2., 0.);
+        assert_approx_eq(p1.delta_phi(&p2), PI);
+        assert_approx_eq(p2.delta_phi(&p1), PI);
+        assert_approx_eq(p1.delta_r(&p2), PI);
+        assert_eq!(p1.delta_r(&p1), 0.);
+    }
+
+    #[test]
+    fn tst_normalize_angles() {
+        use std::f64::consts::PI;
+
+        let particle = |theta, phi| Particle {
+            theta,
+            phi,
+            ..Default::default()
+        };
+        let normalized = |theta, phi| {
+            let mut p = particle(theta, phi);
+            p.normalize_angles();
+            (p.theta, p.phi)
+        };
+        let (theta, phi) = normalized(1., 3. * PI / 2.);
+        assert_eq!(theta, 1.);
+        assert_approx_eq(phi, -PI / 2.);
+        assert_eq!(normalized(0., PI), (0., PI));
+        assert_eq!(normalized(0., -PI), (0., PI));
+        assert_approx_eq(normalized(0., 5. * PI / 2.).1, PI / 2.);
+        assert_approx_eq(normalized(0., -3. * PI / 2.).1, PI / 2.);
+        assert_eq!(normalized(-0.1, 0.5), (0., 0.5));
+        assert_eq!(normalized(4., -0.5), (PI, -0.5));
+
+        let mut event = Event {
+            vertices: vec![Vertex {
+                particles_in: vec![particle(0., 2. * PI)],
+                particles_out: vec![particle(PI + 0.5, 3. * PI / 2.)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        event.normalize_angles();
+        let angles: Vec<_> =
+            event.particles().map(|p| (p.theta, p.phi)).collect();
+        assert_eq!(angles[0], (0., 0.));
+        assert_eq!(angles[1].0, PI);
+        assert_approx_eq(angles[1].1, -PI / 2.);
+    }
+
+    #[test]
+    fn tst_recompute_angles() {
+        use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+        let mut p =
+            Particle::new(22, FourVector::txyz(2., 1., 1., 2f64.sqrt()), 1);
+        p.recompute_angles();
+        assert_approx_eq(p.theta, FRAC_PI_4);
+        assert_approx_eq(p.phi, FRAC_PI_4);
+
+        p.p = FourVector::txyz(1., 0., -1., 0.);
+        p.recompute_angles();
+        assert_approx_eq(p.theta, FRAC_PI_2);
+        assert_approx_eq(p.phi, -FRAC_PI_2);
+
+        p.p = FourVector::txyz(1., 0., 0., -1.);
+        p.recompute_angles();
+        assert_eq!((p.theta, p.phi), (PI, 0.));
+
+        p.p = FourVector::txyz(1., 0., 0., -0.);
+        p.recompute_angles();
+        assert_eq!((p.theta, p.phi), (0., 0.));
+
+        let mut event = Event::new(1).with_vertex(Vertex {
+            particles_out: vec![Particle::new(
+                11,
+                FourVector::txyz(1., -1., 0., 0.),
+                1,
+            )],
+            ..Vertex::default()
+        });
+        event.recompute_angles();
+        let p = event.particles().next().unwrap();
+        assert_eq!((p.theta, p.phi), (FRAC_PI_2, PI));
+    }
+
+    #[test]
+    fn tst_validate() {
+        let particle = |barcode, end_vtx| Particle {
+            barcode,
+            end_vtx,
+            ..Default::default()
+        };
+        let mut event = Event {
+            vertices: vec![
+                Vertex {
+                    barcode: -1,
+                    particles_in: vec![particle(1, -1)],
+                    particles_out: vec![particle(2, -2)],
+                    ..Default::default()
+                },
+                Vertex {
+                    barcode: -2,
+                    particles_out: vec![particle(3, 0)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(event.validate(), Ok(()));
+
+        event.vertices[1].particles_out[0].end_vtx = -3;
+        assert_eq!(
+            event.validate(),
+            Err(ValidationError::DanglingEndVertex {
+                particle: 3,
+                end_vtx: -3
+            })
+        );
+
+        event.vertices[1].particles_out[0].end_vtx = 0;
+        event.vertices[1].particles_out[0].p[1] = f64::NAN;
+        assert_eq!(
+            event.validate(),
+            Err(ValidationError::NonFiniteMomentum(3))
+        );
+
+        event.vertices[1].particles_out[0].p[1] = 0.;
+        event.xs.cross_section = f64::INFINITY;
+        assert_eq!(
+            event.validate(),
+            Err(ValidationError::NonFiniteCrossSection)
+        );
+
+        event.xs.cross_section = 0.;
+        event.vertices[1].barcode = -1;
+        assert_eq!(event.validate(), Err(ValidationError::DuplicateVertex(-1)));
+    }
+
+    #[test]
+    fn tst_conservation() {
+        let particle = |p: FourVector, end_vtx| Particle {
+            p,
+            end_vtx,
+            ..Default::default()
+        };
+        let beam = FourVector::txyz(5., 0., 0., 5.);
+        let out1 = FourVector::txyz(3., 1., 0., 2.);
+        let out2 = FourVector::txyz(2., -1., 0., 3.);
+        let decay = FourVector::txyz(1., -1., 0., 0.);
+        let mut event = Event {
+            vertices: vec![
+                Vertex {
+                    barcode: -1,
+                    particles_in: vec![particle(beam, -1)],
+                    particles_out: vec![particle(out1, 0), particle(out2, -2)],
+                    ..Default::default()
+                },
+                Vertex {
+                    barcode: -2,
+                    particles_out: vec![
+                        particle(decay, 0),
+                        particle(out2 - decay, 0),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(event.vertices[0].momentum_imbalance(), FourVector::new());
+        assert_eq!(event.vertices[1].momentum_imbalance(), out2);
+        assert!(event.check_conservation(1e-10).is_empty());
+
+        event.vertices[1].particles_out[0].p = FourVector::txyz(1., 0., 0., 0.);
+        let violations = event.check_conservation(1e-10);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].0, -2);
+        assert_eq!(violations[0].1, FourVector::txyz(0., 1., 0., 0.));
+        assert!(event.check_conservation(2.).is_empty());
+
+        // a vertex without incoming particles
+        event.vertices[1].particles_out[0].p = decay;
+        event.vertices.push(Vertex {
+            barcode: -3,
+            particles_out: vec![particle(beam, 0)],
+            ..Default::default()
+        });
+        assert_eq!(event.check_conservation(1e-10), [(-3, beam)]);
+        assert!(event.check_conservation_excluding_sources(1e-10).is_empty());
+    }
+
+    #[test]
+    fn tst_status_class() {
+        let particle = |status| Particle {
+            status,
+            ..Default::default()
+        };
+        assert_eq!(particle(0).status_class(), StatusClass::Null);
+        assert_eq!(particle(1).status_class(), StatusClass::Final);
+        assert_eq!(particle(2).status_class(), StatusClass::Decayed);
+        assert_eq!(particle(3).status_class(), StatusClass::Documentation);
+        assert_eq!(particle(4).status_class(), StatusClass::Beam);
+        assert_eq!(particle(62).status_class(), StatusClass::Other(62));
+        assert_eq!(particle(-1).status_class(), StatusClass::Other(-1));
+
+        assert!(particle(1).is_final());
+        assert!(!particle(1).is_decayed());
+        assert!(!particle(1).is_beam());
+        assert!(particle(2).is_decayed());
+        assert!(!particle(2).is_final());
+        assert!(particle(4).is_beam());
+        assert!(!particle(4).is_final());
+        assert!(!particle(201).is_final());
+    }
+
+    #[test]
+    fn tst_boost_superluminal() {
+        let p = FourVector::txyz(1., 0., 0., 0.);
+        assert!(p.boost([0., 0., 0.]).is_ok());
+        assert!(p.boost([0.6, 0.8, 0.]).is_err());
+        assert!(p.boost([2., 0., 0.]).is_err());
+        let massless = FourVector::txyz(1., 0., 0., 1.);
+        assert!(p.boost_to_rest_frame(&massless).is_err());
+    }
+
+    #[test]
+    fn tst_unit_conversion() {
+        use EnergyUnit::*;
+        use LengthUnit::*;
+
+        assert_eq!(EnergyUnit::conversion_factor(MEV, MEV), 1.);
+        assert_eq!(EnergyUnit::conversion_factor(GEV, GEV), 1.);
+        assert_eq!(EnergyUnit::conversion_factor(GEV, MEV), 1e3);
+        assert_eq!(EnergyUnit::conversion_factor(MEV, GEV), 1e-3);
+        assert_eq!(LengthUnit::conversion_factor(CM, MM), 10.);
+        assert_eq!(LengthUnit::conversion_factor(MM, CM), 0.1);
+    }
+
+    #[test]
+    fn tst_unit_display() {
+        use std::str::FromStr;
+
+        for unit in [EnergyUnit::MEV, EnergyUnit::GEV] {
+            let txt = unit.to_string();
+            assert_eq!(EnergyUnit::from_str(&txt), Ok(unit));
+        }
+        for unit in [LengthUnit::MM, LengthUnit::CM] {
+            let txt = unit.to_string();
+            assert_eq!(LengthUnit::from_str(&txt), Ok(unit));
+        }
+        for txt in ["MEV", "GEV"] {
+            assert_eq!(EnergyUnit::from_str(txt).unwrap().to_string(), txt);
+        }
+        for txt in ["MM", "CM"] {
+            assert_eq!(LengthUnit::from_str(txt).unwrap().to_string(), txt);
+        }
+    }
+
+    #[test]
+    fn tst_weight_by_name() {
+        let mut event = Event {
+            weights: vec![1., 2., 3.],
+            weight_names: vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]
+                .into(),
+            ..Default::default()
+        };
+        assert_eq!(event.weight("a"), Some(1.));
+        assert_eq!(event.weight("c"), Some(3.));
+        assert_eq!(event.weight("d"), None);
+        let map: Vec<_> = event.weight_map().collect();
+        assert_eq!(map, [("a", 1.), ("b", 2.), ("c", 3.)]);
+
+        // mismatched lengths
+        event.weights.truncate(2);
+        assert_eq!(event.weight("b"), Some(2.));
+        assert_eq!(event.weight("c"), None);
+        assert_eq!(event.weight_map().count(), 2);
+        event.weights = vec![1., 2., 3., 4.];
+        assert_eq!(event.weight("c"), Some(3.));
+        assert_eq!(event.weight_map().count(), 3);
+    }
+
+    #[test]
+    fn tst_decay_chain() {
+        let particle = |barcode, end_vtx| Particle {
+            barcode,
+            end_vtx,
+            ..Default::default()
+        };
+        let vertex = |barcode, particles_out| Vertex {
+            barcode,
+            particles_out,
+            ..Default::default()
+        };
+        let mut event = Event {
+            vertices: vec![
+                Vertex {
+                    particles_in: vec![particle(1, -1)],
+                    ..vertex(-1, vec![particle(2, -2), particle(3, 0)])
+                },
+                vertex(-2, vec![particle(4, -3), particle(5, 0)]),
+                vertex(-3, vec![particle(6, 0)]),
+            ],
+            ..Default::default()
+        };
+        let barcodes = |particles: Vec<&Particle>| -> Vec<i32> {
+            particles.into_iter().map(|p| p.barcode).collect()
+        };
+        assert_eq!(barcodes(event.children(1).collect()), [2, 3]);
+        assert_eq!(barcodes(event.children(2).collect()), [4, 5]);
+        assert!(event.children(3).next().is_none());
+        assert!(event.children(7).next().is_none());
+        assert_eq!(barcodes(event.descendants(1).collect()), [2, 4, 6, 5, 3]);
+        assert_eq!(barcodes(event.descendants(2).collect()), [4, 6, 5]);
+        assert!(event.descendants(6).next().is_none());
+
+        // cycle
+        event.vertices[2].particles_out[0].end_vtx = -1;
+        assert_eq!(barcodes(event.descendants(1).collect()), [2, 4, 6, 5, 3]);
+        assert_eq!(barcodes(event.descendants(2).collect()), [4, 6, 2, 3, 5]);
+    }
+
+    #[test]
+    fn tst_signal_process_vertex() {
+        let mut event = Event {
+            signal_process_vertex: -2,
+            vertices: vec![
+                Vertex {
+                    barcode: -1,
+                    ..Default::default()
+                },
+                Vertex {
+                    barcode: -2,
+                    status: 3,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let vertex = event.signal_process_vertex_ref().unwrap();
+        assert_eq!(vertex.barcode, -2);
+        assert_eq!(vertex.status, 3);
+
+        event.signal_process_vertex = -3;
+        assert!(event.signal_process_vertex_ref().is_none());
+        event.signal_process_vertex = 0;
+        assert!(event.signal_process_vertex_ref().is_none());
+    }
+
+    #[test]
+    fn tst_set_weight() {
+        let mut event = Event {
+            weights: vec![1.],
+            weight_names: vec!["a".to_owned()].into(),
+            ..Default::default()
+        };
+        event.set_weight("a", 2.);
+        assert_eq!(event.weight("a"), Some(2.));
+        event.set_weight("b", 3.);
+        assert_eq!(event.weight("b"), Some(3.));
+        assert_eq!(event.weights, [2., 3.]);
+        assert_eq!(event.weight_names, ["a", "b"]);
+
+        // unnamed weights
+        event.weights.push(4.);
+        event.set_weight("c", 5.);
+        assert_eq!(event.weight("c"), Some(5.));
+        assert_eq!(event.weight_names, ["a", "b", "2", "c"]);
+        assert_eq!(event.weights, [2., 3., 4., 5.]);
+
+        // names without weights
+        event.weight_names.push("d".to_owned());
+        event.set_weight("e", 6.);
+        assert_eq!(event.weight_names, ["a", "b", "2", "c", "d", "e"]);
+        assert_eq!(event.weights, [2., 3., 4., 5., 0., 6.]);
+        event.weights.truncate(4);
+        event.set_weight("d", 7.);
+        assert_eq!(event.weight("d"), Some(7.));
+
+        // shared names are copied
+        let shared = WeightNames::from(vec!["x".to_owned()]);
+        let mut event = Event {
+            weights: vec![1.],
+            weight_names: shared.clone(),
+            ..Default::default()
+        };
+        event.set_weight("y", 2.);
+        assert_eq!(event.weight_names, ["x", "y"]);
+        assert_eq!(event.weight("x"), Some(1.));
+        assert_eq!(shared, ["x"]);
+    }
+
+    #[test]
+    fn tst_reconcile_weights() {
+        let event = Event {
+            weights: vec![1., 2., 3.],
+            weight_names: vec!["a".to_owned(), "b".to_owned()].into(),
+            ..Default::default()
+        };
+
+        let mut leave = event.clone();
+        leave.reconcile_weights(WeightRepair::Leave);
+        assert_eq!(leave, event);
+
+        let mut truncated = event.clone();
+        truncated.reconcile_weights(WeightRepair::TruncateToNames);
+        assert_eq!(truncated.weights, [1., 2.]);
+        assert_eq!(truncated.weight_names, ["a", "b"]);
+
+        let mut padded = event.clone();
+        padded.reconcile_weights(WeightRepair::PadNames("w".to_owned()));
+        assert_eq!(padded.weights, [1., 2., 3.]);
+        assert_eq!(padded.weight_names, ["a", "b", "w2"]);
+
+        // more names than weights
+        let mut event = Event {
+            weights: vec![1.],
+            weight_names: vec!["a".to_owned(), "b".to_owned()].into(),
+            ..Default::default()
+        };
+        event.reconcile_weights(WeightRepair::PadNames(String::new()));
+        assert_eq!(event.weight_names, ["a"]);
+        assert_eq!(event.weights, [1.]);
+
+        // unnamed weights are left alone
+        let mut event = Event::new(0).with_weight(1.).with_weight(2.);
+        event.reconcile_weights(WeightRepair::TruncateToNames);
+        assert_eq!(event.weights, [1., 2.]);
     }
 }
@@ -0,0 +1,377 @@
+//! Output in the HepMC3 format
+//!
+//! [HepMC3Writer] writes events in the `HepMC::Asciiv3` format read
+//! by `HepMC3::ReaderAscii`. The conversion is as follows:
+//!
+//! - Particles and vertices are numbered in the order of
+//!   [Event::particles] and [Event::vertices], starting from 1 and
+//!   -1, respectively. Original barcodes are not preserved.
+//! - Every vertex is written explicitly as a `V` line listing its
+//!   incoming particles. Orphan particles (`particles_in`) are
+//!   written without a production vertex just before their end
+//!   vertex, outgoing particles just after their production vertex.
+//!   Vertex positions are only written if they are non-zero.
+//! - Weight names are written in the run information before the
+//!   first event.
+//! - The event scale, coupling constants, signal process id, signal
+//!   process vertex, number of multi-parton interactions, random
+//!   states, cross section, and PDF information are written as event
+//!   attributes with the names used by HepMC3 when reading HepMC2
+//!   input. Particle flows, polarisation angles, and vertex weights
+//!   are written as particle and vertex attributes.
+//!
+//! The conversion is lossy in the following respects:
+//!
+//! - Particle and vertex barcodes are replaced by HepMC3 ids.
+//! - Heavy ion information is dropped.
+//! - Unparsed record lines ([Event::raw_extra]) are dropped.
+//! - HepMC3 separates weight names by whitespace, so names
+//!   containing whitespace cannot be read back correctly.
+//! - If a vertex has incoming particles that are produced at a later
+//!   vertex, the `V` line refers to particles that have not been
+//!   written yet. Events read by this crate are usually ordered such
+//!   that this does not happen.
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+
+use crate::event::*;
+use crate::writer::{maybe_write, Float};
+
+use hepmc2_macros::write_bound;
+use log::error;
+
+const HEADER: &[u8] = b"HepMC::Version 3.02.06
+HepMC::Asciiv3-START_EVENT_LISTING
+";
+
+const FOOTER: &[u8] = b"HepMC::Asciiv3-END_EVENT_LISTING\n";
+
+/// Writer for the HepMC3 format
+///
+/// See the [module documentation](self) for the details of the
+/// conversion.
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::hepmc3::HepMC3Writer;
+/// use hepmc2::event::Event;
+///
+/// let mut output = Vec::new();
+/// let mut writer = HepMC3Writer::new(&mut output)?;
+/// writer.write(&Event::default())?;
+/// // always call finish at the end
+/// writer.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[write_bound]
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct HepMC3Writer<T> {
+    stream: T,
+    finished: bool,
+    run_info_written: bool,
+}
+
+#[write_bound]
+impl<T> HepMC3Writer<T> {
+    /// Construct new `HepMC3Writer`
+    ///
+    /// This automatically tries to write the mandatory HepMC3 header,
+    /// which may fail.
+    #[maybe_async::maybe_async]
+    pub async fn new(mut stream: T) -> Result<Self, io::Error> {
+        stream.write_all(HEADER).await?;
+        Ok(Self {
+            stream,
+            finished: false,
+            run_info_written: false,
+        })
+    }
+
+    /// Finish writing, consuming the `HepMC3Writer`
+    ///
+    /// This tries to write the mandatory HepMC3 footer and then
+    /// flushes (or shuts down, for the async API) the underlying
+    /// stream, which may fail.
+    #[maybe_async::maybe_async]
+    pub async fn finish(mut self) -> Result<(), io::Error> {
+        self.ref_finish().await
+    }
+
+    /// Write an event
+    ///
+    /// The weight names of the first event are written as run
+    /// information.
+    #[maybe_async::maybe_async]
+    pub async fn write(&mut self, event: &Event) -> Result<(), io::Error> {
+        if !self.run_info_written {
+            if !event.weight_names.is_empty() {
+                self.stream.write_all(b"W").await?;
+                for name in &event.weight_names {
+                    maybe_write!(self.stream, " {}", name);
+                }
+                self.stream.write_all(b"\n").await?;
+            }
+            self.run_info_written = true;
+        }
+        let nparticles = event.particles().count();
+        maybe_write!(
+            self.stream,
+            "E {} {} {}\n",
+            event.number,
+            event.vertices.len(),
+            nparticles
+        );
+        maybe_write!(
+            self.stream,
+            "U {} {}\n",
+            event.energy_unit,
+            event.length_unit
+        );
+        if !event.weights.is_empty() {
+            self.stream.write_all(b"W").await?;
+            for weight in &event.weights {
+                maybe_write!(self.stream, " {}", Float::shortest(*weight));
+            }
+            self.stream.write_all(b"\n").await?;
+        }
+        self.write_event_attributes(event).await?;
+        self.write_particle_attributes(event).await?;
+
+        let incoming = incoming_particle_ids(event);
+        let mut particle_id = 0;
+        for (n, vertex) in event.vertices.iter().enumerate() {
+            for particle in &vertex.particles_in {
+                particle_id += 1;
+                self.write_particle_line(particle_id, 0, particle).await?;
+            }
+            let vertex_id = -(n as i64) - 1;
+            self.write_vertex_line(vertex_id, vertex, &incoming[n])
+                .await?;
+            for particle in &vertex.particles_out {
+                particle_id += 1;
+                self.write_particle_line(particle_id, vertex_id, particle)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[maybe_async::maybe_async]
+    async fn ref_finish(&mut self) -> Result<(), io::Error> {
+        self.stream.write_all(FOOTER).await?;
+        self.finished = true;
+        #[cfg(feature = "sync")]
+        return self.stream.flush();
+        #[cfg(feature = "tokio")]
+        return self.stream.shutdown().await;
+    }
+
+    #[maybe_async::maybe_async]
+    async fn write_event_attributes(
+        &mut self,
+        event: &Event,
+    ) -> Result<(), io::Error> {
+        maybe_write!(
+            self.stream,
+            "A 0 GenCrossSection {} {} -1 -1\n",
+            Float::shortest(event.xs.cross_section),
+            Float::shortest(event.xs.cross_section_error)
+        );
+        let pdf = &event.pdf_info;
+        maybe_write!(
+            self.stream,
+            "A 0 GenPdfInfo {} {} {} {} {} {} {} {} {}\n",
+            pdf.parton_id[0],
+            pdf.parton_id[1],
+            Float::shortest(pdf.x[0]),
+            Float::shortest(pdf.x[1]),
+            Float::shortest(pdf.scale),
+            Float::shortest(pdf.xf[0]),
+            Float::shortest(pdf.xf[1]),
+            pdf.pdf_id[0],
+            pdf.pdf_id[1],
+        );
+        maybe_write!(
+            self.stream,
+            "A 0 alphaQCD {}\n",
+            Float::shortest(event.alpha_qcd)
+        );
+        maybe_write!(
+            self.stream,
+            "A 0 alphaQED {}\n",
+            Float::shortest(event.alpha_qed)
+        );
+        maybe_write!(
+            self.stream,
+            "A 0 event_scale {}\n",
+            Float::shortest(event.scale)
+        );
+        maybe_write!(self.stream, "A 0 mpi {}\n", event.mpi);
+        maybe_write!(
+            self.stream,
+            "A 0 signal_process_id {}\n",
+            event.signal_process_id
+        );
+        let signal_vertex = event
+            .vertices
+            .iter()
+            .position(|v| v.barcode == event.signal_process_vertex);
+        if let Some(n) = signal_vertex {
+            maybe_write!(
+                self.stream,
+                "A 0 signal_process_vertex {}\n",
+                -(n as i64) - 1
+            );
+        }
+        if !event.random_states.is_empty() {
+            self.stream.write_all(b"A 0 random_states").await?;
+            for state in &event.random_states {
+                maybe_write!(self.stream, " {}", state);
+            }
+            self.stream.write_all(b"\n").await?;
+        }
+        for (n, vertex) in event.vertices.iter().enumerate() {
+            if vertex.weights.is_empty() {
+                continue;
+            }
+            maybe_write!(self.stream, "A {} weights", -(n as i64) - 1);
+            for weight in &vertex.weights {
+                maybe_write!(self.stream, " {}", Float::shortest(*weight));
+            }
+            self.stream.write_all(b"\n").await?;
+        }
+        Ok(())
+    }
+
+    #[maybe_async::maybe_async]
+    async fn write_particle_attributes(
+        &mut self,
+        event: &Event,
+    ) -> Result<(), io::Error> {
+        for (particle, id) in event.particles().zip(1..) {
+            for (idx, val) in &particle.flows {
+                maybe_write!(self.stream, "A {} flow{} {}\n", id, idx, val);
+            }
+            if particle.theta != 0. {
+                maybe_write!(
+                    self.stream,
+                    "A {} theta {}\n",
+                    id,
+                    Float::shortest(particle.theta)
+                );
+            }
+            if particle.phi != 0. {
+                maybe_write!(
+                    self.stream,
+                    "A {} phi {}\n",
+                    id,
+                    Float::shortest(particle.phi)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[maybe_async::maybe_async]
+    async fn write_vertex_line(
+        &mut self,
+        id: i64,
+        vertex: &Vertex,
+        incoming: &[usize],
+    ) -> Result<(), io::Error> {
+        let mut ids = String::new();
+        for (n, particle_id) in incoming.iter().enumerate() {
+            if n > 0 {
+                ids.push(',');
+            }
+            write!(ids, "{particle_id}").unwrap();
+        }
+        maybe_write!(self.stream, "V {} {} [{}]", id, vertex.status, ids);
+        let position = [vertex.x, vertex.y, vertex.z, vertex.t];
+        if position.iter().any(|&x| x != 0.) {
+            maybe_write!(
+                self.stream,
+                " @ {} {} {} {}",
+                Float::shortest(vertex.x),
+                Float::shortest(vertex.y),
+                Float::shortest(vertex.z),
+                Float::shortest(vertex.t)
+            );
+        }
+        self.stream.write_all(b"\n").await
+    }
+
+    #[maybe_async::maybe_async]
+    async fn write_particle_line(
+        &mut self,
+        id: usize,
+        production_vertex: i64,
+        particle: &Particle,
+    ) -> Result<(), io::Error> {
+        maybe_write!(
+            self.stream,
+            "P {} {} {} {} {} {} {} {} {}\n",
+            id,
+            production_vertex,
+            particle.id,
+            Float::shortest(particle.p[1]),
+            Float::shortest(particle.p[2]),
+            Float::shortest(particle.p[3]),
+            Float::shortest(particle.p[0]),
+            Float::shortest(particle.m),
+            particle.status
+        );
+        Ok(())
+    }
+}
+
+#[write_bound]
+impl<T> Drop for HepMC3Writer<T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            error!("HepMC3 writer dropped before finished.");
+            error!("Call finish() manually to fix this error.");
+            #[cfg(feature = "sync")]
+            if let Err(err) = self.ref_finish() {
+                error!("Error writing footer: {}", err);
+            }
+            #[cfg(feature = "tokio")]
+            tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(async {
+                    if let Err(err) = self.ref_finish().await {
+                        error!("Error writing footer: {}", err);
+                    }
+                })
+            });
+        }
+    }
+}
+
+/// HepMC3 ids of the incoming particles for each vertex
+fn incoming_particle_ids(event: &Event) -> Vec<Vec<usize>> {
+    let mut vertex_pos = HashMap::new();
+    for (n, vertex) in event.vertices.iter().enumerate() {
+        vertex_pos.entry(vertex.barcode).or_insert(n);
+    }
+    let mut incoming = vec![Vec::new(); event.vertices.len()];
+    let mut particle_id = 0;
+    for (n, vertex) in event.vertices.iter().enumerate() {
+        for _ in &vertex.particles_in {
+            particle_id += 1;
+            incoming[n].push(particle_id);
+        }
+        for particle in &vertex.particles_out {
+            particle_id += 1;
+            if particle.end_vtx == 0 {
+                continue;
+            }
+            if let Some(&end) = vertex_pos.get(&particle.end_vtx) {
+                incoming[end].push(particle_id);
+            }
+        }
+    }
+    incoming
+}
@@ -0,0 +1,50 @@
+//! Wrapper for fields that do not take part in comparisons
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+
+/// Value that is ignored in comparisons and hashing
+///
+/// All `Ignored` values compare equal. This allows deriving the
+/// comparison traits for types with fields that are internal state
+/// rather than part of the value.
+#[derive(Debug, Default)]
+pub(crate) struct Ignored<T>(pub(crate) T);
+
+impl<T> Deref for Ignored<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Ignored<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> PartialEq for Ignored<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Ignored<T> {}
+
+impl<T> PartialOrd for Ignored<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ignored<T> {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl<T> Hash for Ignored<T> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
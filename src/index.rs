@@ -0,0 +1,319 @@
+//! Random access to events via an index of byte offsets
+use std::io::{self, Read, Write};
+
+use crate::event::Event;
+use crate::reader::{LineParseError, ParseError, Reader};
+
+use hepmc2_macros::{read_bound, seek_bound};
+#[cfg(all(feature = "rayon", feature = "sync"))]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Byte offsets of the events in a stream
+///
+/// The offset of the `n`th event (counting from zero) is at position
+/// `n`. There is a simple binary format for storing the index in a
+/// sidecar file, see [write_to](Self::write_to) and
+/// [read_from](Self::read_from). With the `serde` feature, the index
+/// can also be serialised with `serde`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventIndex {
+    offsets: Vec<u64>,
+}
+
+impl EventIndex {
+    /// Construct an index from event offsets
+    pub fn new(offsets: Vec<u64>) -> Self {
+        Self { offsets }
+    }
+
+    /// The byte offsets of all events
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// The byte offset of the `n`th event
+    pub fn get(&self, n: usize) -> Option<u64> {
+        self.offsets.get(n).copied()
+    }
+
+    /// The number of indexed events
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Write the index in binary format
+    ///
+    /// The format is the number of events followed by the offsets,
+    /// each as a little-endian `u64`.
+    pub fn write_to<W: Write>(&self, mut out: W) -> Result<(), io::Error> {
+        out.write_all(&(self.offsets.len() as u64).to_le_bytes())?;
+        for offset in &self.offsets {
+            out.write_all(&offset.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    /// Read an index written with [write_to](Self::write_to)
+    pub fn read_from<R: Read>(mut input: R) -> Result<Self, io::Error> {
+        let len = read_u64(&mut input)?;
+        let len = usize::try_from(len)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let offsets = (0..len)
+            .map(|_| read_u64(&mut input))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { offsets })
+    }
+}
+
+#[cfg(all(feature = "rayon", feature = "sync"))]
+impl EventIndex {
+    /// Read the indexed events in parallel
+    ///
+    /// This is only available with the `rayon` and `sync` features.
+    /// The indexed events are split into contiguous chunks, and for
+    /// each chunk a new stream is obtained from `open` and parsed
+    /// independently. The events are yielded in the same order as in
+    /// the stream.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use hepmc2::index::IndexedReader;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// use std::io::BufReader;
+    /// use std::fs::File;
+    ///
+    /// let open = || File::open("events.hepmc2").map(BufReader::new);
+    /// let reader = IndexedReader::new(open()?)?;
+    /// let nevents = reader
+    ///     .index()
+    ///     .par_events(open)
+    ///     .filter(|event| event.is_ok())
+    ///     .count();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_events<'a, T, F>(
+        &'a self,
+        open: F,
+    ) -> impl ParallelIterator<Item = Result<Event, LineParseError>> + 'a
+    where
+        T: std::io::BufRead + std::io::Seek,
+        F: Fn() -> Result<T, io::Error> + Send + Sync + 'a,
+    {
+        let nchunks = 4 * rayon::current_num_threads();
+        let chunk_size = self.len().div_ceil(nchunks).max(1);
+        self.offsets
+            .par_chunks(chunk_size)
+            .flat_map_iter(move |chunk| {
+                let mut reader = match open() {
+                    Ok(stream) => Reader::from(stream),
+                    Err(err) => return ChunkEvents::Failed(Some(err)),
+                };
+                if let Err(err) = reader.seek_to_offset(chunk[0]) {
+                    return ChunkEvents::Failed(Some(err));
+                }
+                ChunkEvents::Events(reader.take(chunk.len()))
+            })
+    }
+}
+
+/// Events from one chunk of a parallel read
+#[cfg(all(feature = "rayon", feature = "sync"))]
+enum ChunkEvents<T> {
+    Events(std::iter::Take<Reader<T>>),
+    Failed(Option<io::Error>),
+}
+
+#[cfg(all(feature = "rayon", feature = "sync"))]
+impl<T: std::io::BufRead> Iterator for ChunkEvents<T> {
+    type Item = Result<Event, LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Events(events) => events.next(),
+            Self::Failed(err) => err.take().map(|err| Err(io_line_err(err))),
+        }
+    }
+}
+
+fn read_u64<R: Read>(input: &mut R) -> Result<u64, io::Error> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reader with random access to events
+///
+/// This requires a seekable stream. Besides random access with
+/// [event](Self::event), the events can be iterated over from both
+/// ends.
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```no_run")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::index::IndexedReader;
+///
+/// use std::io::BufReader;
+/// use std::fs::File;
+///
+/// let input = BufReader::new(File::open("events.hepmc2")?);
+/// let mut reader = IndexedReader::new(input)?;
+/// let last = reader.len() - 1;
+/// let event = reader.event(last)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct IndexedReader<T> {
+    reader: Reader<T>,
+    index: EventIndex,
+    front: usize,
+    back: usize,
+}
+
+impl<T> IndexedReader<T> {
+    /// The event index
+    pub fn index(&self) -> &EventIndex {
+        &self.index
+    }
+
+    /// The number of events
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Whether there are no events
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> T {
+        self.reader.into_inner()
+    }
+}
+
+#[read_bound]
+#[seek_bound]
+impl<T> IndexedReader<T> {
+    /// Construct a new IndexedReader, building the index in one pass
+    /// over the stream
+    #[maybe_async::maybe_async]
+    pub async fn new(stream: T) -> Result<Self, LineParseError> {
+        let mut reader = Reader::from(stream);
+        reader.rewind().await.map_err(io_line_err)?;
+        let mut offsets = Vec::new();
+        while reader.skip_event().await? {
+            offsets.push(reader.event_offset().unwrap());
+        }
+        let back = offsets.len();
+        Ok(Self {
+            reader,
+            index: EventIndex::new(offsets),
+            front: 0,
+            back,
+        })
+    }
+
+    /// Construct a new IndexedReader from a previously built index
+    pub fn with_index(stream: T, index: EventIndex) -> Self {
+        let back = index.len();
+        Self {
+            reader: Reader::from(stream),
+            index,
+            front: 0,
+            back,
+        }
+    }
+
+    /// Read the `n`th event, counting from zero
+    #[maybe_async::maybe_async]
+    pub async fn event(&mut self, n: usize) -> Result<Event, LineParseError> {
+        let Some(offset) = self.index.get(n) else {
+            return Err(LineParseError::new(
+                ParseError::NoEvent,
+                String::new(),
+                0,
+            ));
+        };
+        self.reader
+            .seek_to_offset(offset)
+            .await
+            .map_err(io_line_err)?;
+        match self.reader.next().await {
+            Some(event) => event,
+            None => {
+                Err(LineParseError::new(ParseError::NoEvent, String::new(), 0))
+            }
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_front(&mut self) -> Option<Result<Event, LineParseError>> {
+        if self.front >= self.back {
+            return None;
+        }
+        let event = self.event(self.front).await;
+        self.front += 1;
+        Some(event)
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_from_back(
+        &mut self,
+    ) -> Option<Result<Event, LineParseError>> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.event(self.back).await)
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next event from the front
+    pub async fn next(&mut self) -> Option<Result<Event, LineParseError>> {
+        self.next_front().await
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next event from the back
+    pub async fn next_back(&mut self) -> Option<Result<Event, LineParseError>> {
+        self.next_from_back().await
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead + std::io::Seek> Iterator for IndexedReader<T> {
+    type Item = Result<Event, LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead + std::io::Seek> DoubleEndedIterator
+    for IndexedReader<T>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_from_back()
+    }
+}
+
+fn io_line_err(err: io::Error) -> LineParseError {
+    LineParseError::new(err.into(), String::new(), 0)
+}
@@ -0,0 +1,228 @@
+//! Newline-delimited JSON input and output
+//!
+//! This module is only available with the `json` feature. Each
+//! event is stored as a single JSON object on its own line, using
+//! the `serde` representation of [Event].
+//!
+//! For single events, [event_to_json] and [event_from_json] convert
+//! to and from JSON strings. Floating-point numbers round-trip
+//! exactly and [FourVector](crate::event::FourVector)s are
+//! represented as arrays of four numbers.
+//!
+//! Non-finite floating-point numbers cannot be represented in JSON.
+//! Events containing them are rejected by both [JsonWriter] and
+//! [event_to_json].
+use std::io;
+
+use crate::event::Event;
+
+use hepmc2_macros::{read_bound, write_bound};
+use thiserror::Error;
+
+const BUF_SIZE: usize = 256;
+
+/// Writer for newline-delimited JSON
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct JsonWriter<T> {
+    stream: T,
+    buf: Vec<u8>,
+}
+
+impl<T> JsonWriter<T> {
+    /// Retrieve the underlying writer
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+#[write_bound]
+impl<T> From<T> for JsonWriter<T> {
+    fn from(stream: T) -> Self {
+        Self {
+            stream,
+            buf: Vec::new(),
+        }
+    }
+}
+
+#[write_bound]
+impl<T> JsonWriter<T> {
+    /// Construct a new `JsonWriter`
+    pub fn new(stream: T) -> Self {
+        stream.into()
+    }
+
+    /// Write an event as a single line
+    ///
+    /// Fails with [io::ErrorKind::InvalidData] if any floating-point
+    /// number in the event is infinite or NaN.
+    #[maybe_async::maybe_async]
+    pub async fn write(&mut self, event: &Event) -> Result<(), io::Error> {
+        if !is_finite(event) {
+            let err = JsonError::NonFinite(event.number);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+        self.buf.clear();
+        serde_json::to_writer(&mut self.buf, event)?;
+        self.buf.push(b'\n');
+        self.stream.write_all(&self.buf).await
+    }
+
+    /// Finish writing, consuming the `JsonWriter`
+    ///
+    /// This flushes (or shuts down, for the async API) the underlying
+    /// stream, which may fail.
+    #[maybe_async::maybe_async]
+    pub async fn finish(mut self) -> Result<(), io::Error> {
+        #[cfg(feature = "sync")]
+        return self.stream.flush();
+        #[cfg(feature = "tokio")]
+        return self.stream.shutdown().await;
+    }
+}
+
+/// Reader for newline-delimited JSON
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct JsonReader<T> {
+    stream: T,
+    line: String,
+    line_nr: usize,
+}
+
+impl<T> JsonReader<T> {
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+#[read_bound]
+impl<T> From<T> for JsonReader<T> {
+    fn from(stream: T) -> Self {
+        Self {
+            stream,
+            line: String::with_capacity(BUF_SIZE),
+            line_nr: 0,
+        }
+    }
+}
+
+#[read_bound]
+impl<T> JsonReader<T> {
+    /// Construct a new `JsonReader`
+    pub fn new(stream: T) -> Self {
+        stream.into()
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_event(&mut self) -> Option<Result<Event, JsonError>> {
+        loop {
+            self.line.clear();
+            match self.stream.read_line(&mut self.line).await {
+                Ok(0) => return None,
+                Ok(_) => self.line_nr += 1,
+                Err(err) => return Some(Err(JsonError::Io(err))),
+            }
+            if self.line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&self.line).map_err(|err| {
+                JsonError::Json {
+                    err,
+                    line_nr: self.line_nr,
+                }
+            });
+            return Some(event);
+        }
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next event from the stream
+    pub async fn next(&mut self) -> Option<Result<Event, JsonError>> {
+        self.next_event().await
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead> Iterator for JsonReader<T> {
+    type Item = Result<Event, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event()
+    }
+}
+
+/// Serialize an event to a JSON string
+///
+/// Fails with [JsonError::NonFinite] if any floating-point number in
+/// the event is infinite or NaN.
+pub fn event_to_json(event: &Event) -> Result<String, JsonError> {
+    if !is_finite(event) {
+        return Err(JsonError::NonFinite(event.number));
+    }
+    Ok(serde_json::to_string(event)?)
+}
+
+/// Deserialize an event from a JSON string
+pub fn event_from_json(json: &str) -> Result<Event, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+fn is_finite(event: &Event) -> bool {
+    let xs = &event.xs;
+    let pdf = &event.pdf_info;
+    let mut floats = [
+        event.scale,
+        event.alpha_qcd,
+        event.alpha_qed,
+        xs.cross_section,
+        xs.cross_section_error,
+        pdf.scale,
+    ]
+    .into_iter()
+    .chain(event.weights.iter().copied())
+    .chain(pdf.x)
+    .chain(pdf.xf);
+    if !floats.all(f64::is_finite) {
+        return false;
+    }
+    if let Some(hi) = &event.heavy_ion_info {
+        let floats = [
+            hi.impact_parameter,
+            hi.event_plane_angle,
+            hi.eccentricity,
+            hi.sigma_inel_nn,
+        ];
+        if !floats.into_iter().all(f64::is_finite) {
+            return false;
+        }
+    }
+    event.vertices.iter().all(|vx| {
+        [vx.x, vx.y, vx.z, vx.t]
+            .into_iter()
+            .chain(vx.weights.iter().copied())
+            .all(f64::is_finite)
+    }) && event.particles().all(|p| {
+        [p.m, p.theta, p.phi]
+            .into_iter()
+            .chain(p.p.0)
+            .all(f64::is_finite)
+    })
+}
+
+/// Error for newline-delimited JSON
+#[derive(Debug, Error)]
+pub enum JsonError {
+    #[error("I/O error")]
+    Io(#[from] io::Error),
+    #[error("Event {0} contains non-finite floating-point numbers")]
+    NonFinite(i32),
+    #[error("JSON error")]
+    Serde(#[from] serde_json::Error),
+    #[error("JSON error in line {line_nr}")]
+    Json {
+        #[source]
+        err: serde_json::Error,
+        line_nr: usize,
+    },
+}
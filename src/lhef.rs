@@ -0,0 +1,105 @@
+//! Export to the Les Houches Event Format
+//!
+//! [write_event] writes a single `<event>` block in the format of
+//! [hep-ph/0609017](https://arxiv.org/abs/hep-ph/0609017). The
+//! surrounding `<LesHouchesEvents>` document, including the `<init>`
+//! block, is not written.
+//!
+//! By default, only beam particles and particles with status 1 are
+//! exported, see [write_event_with] for a custom selection.
+//! Exported beam particles are incoming (`ISTUP = -1`), particles with
+//! status 1 are outgoing (`ISTUP = 1`), and all other particles are
+//! intermediate (`ISTUP = 2`). The mothers of non-incoming particles
+//! are set to the range of all incoming particles. Colour flows are
+//! taken from the [flows](crate::event::Particle::flows) with index 1
+//! (colour) and 2 (anticolour). Spin information is not available and
+//! written as 9.
+use std::io::{self, Write};
+
+use crate::event::{Event, Particle};
+use crate::writer::Float;
+
+/// Write an event as an LHEF `<event>` block
+///
+/// Only beam particles and final-state particles are exported. The
+/// process id is the [signal_process_id](Event::signal_process_id)
+/// and the weight is the first event weight, or 1 if the event has no
+/// weights.
+///
+/// # Example
+///
+/// ```
+/// use hepmc2::event::Event;
+///
+/// let mut output = Vec::new();
+/// hepmc2::lhef::write_event(&mut output, &Event::default())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn write_event<W: Write>(out: W, event: &Event) -> Result<(), io::Error> {
+    write_event_with(out, event, |p| p.is_beam() || p.is_final())
+}
+
+/// Write the particles selected by `select` as an LHEF `<event>` block
+///
+/// See [write_event] for details.
+pub fn write_event_with<W, F>(
+    mut out: W,
+    event: &Event,
+    mut select: F,
+) -> Result<(), io::Error>
+where
+    W: Write,
+    F: FnMut(&Particle) -> bool,
+{
+    let particles: Vec<_> = event.particles().filter(|p| select(p)).collect();
+    let nincoming = particles.iter().filter(|p| p.is_beam()).count();
+    let weight = event.weights.first().copied().unwrap_or(1.);
+    writeln!(out, "<event>")?;
+    writeln!(
+        out,
+        "{} {} {} {} {} {}",
+        particles.len(),
+        event.signal_process_id,
+        Float::shortest(weight),
+        Float::shortest(event.scale),
+        Float::shortest(event.alpha_qed),
+        Float::shortest(event.alpha_qcd)
+    )?;
+    // write incoming particles first, so mothers can refer to them
+    let incoming = particles.iter().filter(|p| p.is_beam());
+    let other = particles.iter().filter(|p| !p.is_beam());
+    for particle in incoming.chain(other) {
+        let (status, mothers) = if particle.is_beam() {
+            (-1, [0, 0])
+        } else if particle.is_final() {
+            (1, mothers(nincoming))
+        } else {
+            (2, mothers(nincoming))
+        };
+        let colour = |idx| particle.flows.get(&idx).copied().unwrap_or(0);
+        writeln!(
+            out,
+            "{} {} {} {} {} {} {} {} {} {} {} 0 9",
+            particle.id,
+            status,
+            mothers[0],
+            mothers[1],
+            colour(1),
+            colour(2),
+            Float::shortest(particle.p[1]),
+            Float::shortest(particle.p[2]),
+            Float::shortest(particle.p[3]),
+            Float::shortest(particle.p[0]),
+            Float::shortest(particle.m),
+        )?;
+    }
+    writeln!(out, "</event>")
+}
+
+fn mothers(nincoming: usize) -> [usize; 2] {
+    if nincoming == 0 {
+        [0, 0]
+    } else {
+        [1, nincoming]
+    }
+}
@@ -87,8 +87,25 @@
 //! # tokio_test::block_on(async {try_main().await.unwrap()})
 //! ```
 
+pub mod compression;
+#[cfg(feature = "csv")]
+pub mod csv;
 pub mod event;
+pub mod hepmc3;
+mod ignored;
+pub mod index;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lhef;
+#[cfg(all(feature = "mmap", feature = "sync"))]
+pub mod mmap;
+#[cfg(feature = "pdg")]
+pub mod pdg;
 pub mod reader;
+pub mod record;
+pub mod stats;
+#[cfg(feature = "sync")]
+pub mod threaded;
 pub mod writer;
 
 pub use crate::event::Event;
@@ -149,6 +166,2898 @@ mod tests {
         assert_eq!(from_utf8(&buf), from_utf8(&buf2));
     }
 
+    #[maybe_async::maybe_async]
+    async fn sample_event() -> Event {
+        let mut reader = reader::Reader::from(EVENT_TXT);
+        reader.next().await.unwrap().unwrap()
+    }
+
+    /// Copies of the sample event, numbered from 0 to `n` - 1
+    #[maybe_async::maybe_async]
+    async fn sample_events(n: i32) -> Vec<u8> {
+        let mut event = sample_event().await;
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        for number in 0..n {
+            event.number = number;
+            writer.write(&event).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+        buf
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_skip_event() {
+        let buf = sample_events(2).await;
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let skipped = reader.skip_event().await.unwrap();
+        assert!(skipped);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 1);
+        let skipped = reader.skip_event().await.unwrap();
+        assert!(!skipped);
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+        let skipped = reader.skip_event().await.unwrap();
+        assert!(skipped);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_skip_events() {
+        let buf = sample_events(5).await;
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let skipped = reader.skip_events(0).await.unwrap();
+        assert_eq!(skipped, 0);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+
+        let skipped = reader.skip_events(2).await.unwrap();
+        assert_eq!(skipped, 2);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 3);
+
+        let skipped = reader.skip_events(10).await.unwrap();
+        assert_eq!(skipped, 1);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_skip_malformed() {
+        let buf = sample_events(3).await;
+        let txt = String::from_utf8(buf).unwrap();
+        let mut nevents = 0;
+        let mut corrupted = false;
+        let mut lines = Vec::new();
+        for line in txt.lines() {
+            if line.starts_with('E') {
+                nevents += 1;
+            }
+            if nevents == 2 && !corrupted && line.starts_with('P') {
+                lines.push("P garbage");
+                corrupted = true;
+            } else {
+                lines.push(line);
+            }
+        }
+        let txt = lines.join("\n");
+
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        let event = reader.next().await.unwrap();
+        assert!(event.is_err());
+
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        reader.set_error_policy(reader::ErrorPolicy::SkipEvent);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 2);
+        let next = reader.next().await;
+        assert!(next.is_none());
+        assert_eq!(reader.errors().len(), 1);
+        assert_eq!(reader.errors()[0].line.trim(), "P garbage");
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_parse_event() {
+        let expected = sample_event().await;
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let event = reader::parse_event(txt).unwrap();
+        assert_eq!(event, expected);
+        let event: Event = txt.parse().unwrap();
+        assert_eq!(event, expected);
+
+        let err = reader::parse_event("HepMC::Version 2.06.09\n").unwrap_err();
+        assert!(matches!(err.err, reader::ParseError::NoEvent));
+
+        let buf = sample_events(2).await;
+        let txt = std::str::from_utf8(&buf).unwrap();
+        let err = reader::parse_event(txt).unwrap_err();
+        assert!(matches!(err.err, reader::ParseError::MultipleEvents));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_version() {
+        let mut reader = reader::Reader::from(EVENT_TXT);
+        assert_eq!(reader.version(), None);
+        reader.read_header().await.unwrap();
+        assert_eq!(reader.version(), Some("2.06.09"));
+        assert_eq!(
+            reader.banner(),
+            Some("HepMC::IO_GenEvent-START_EVENT_LISTING")
+        );
+
+        let mut reader = reader::Reader::from(EVENT_TXT);
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        assert_eq!(reader.version(), Some("2.06.09"));
+
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let txt: String = txt
+            .lines()
+            .filter(|l| !l.starts_with("HepMC::Version"))
+            .flat_map(|l| [l, "\n"])
+            .collect();
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        assert_eq!(reader.version(), None);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_count_events() {
+        let buf = sample_events(2).await;
+        let reader = reader::Reader::from(buf.as_slice());
+        let count = reader.count_events().await.unwrap();
+        assert_eq!(count, 2);
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        let count = reader.count_events().await.unwrap();
+        assert_eq!(count, 1);
+
+        let reader = reader::Reader::from(EVENT_TXT);
+        let count = reader.count_events().await.unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_rewind() {
+        let buf = sample_events(3).await;
+        let mut reader = reader::Reader::from(std::io::Cursor::new(buf));
+        let mut counts = [0; 2];
+        for count in &mut counts {
+            reader.rewind().await.unwrap();
+            let mut next = reader.next().await;
+            while let Some(event) = next {
+                assert!(event.is_ok());
+                *count += 1;
+                next = reader.next().await;
+            }
+        }
+        assert_eq!(counts, [3, 3]);
+
+        reader.rewind().await.unwrap();
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_event_offset() {
+        let buf = sample_events(3).await;
+        let mut reader = reader::Reader::from(std::io::Cursor::new(buf));
+        assert_eq!(reader.event_offset(), None);
+        let mut offsets = Vec::new();
+        let mut next = reader.next().await;
+        while let Some(event) = next {
+            assert!(event.is_ok());
+            offsets.push(reader.event_offset().unwrap());
+            next = reader.next().await;
+        }
+        assert_eq!(offsets.len(), 3);
+
+        let buf = reader.into_inner().into_inner();
+        for &offset in &offsets {
+            assert!(buf[offset as usize..].starts_with(b"E "));
+        }
+
+        let mut reader = reader::Reader::from(std::io::Cursor::new(buf));
+        reader.seek_to_offset(offsets[1]).await.unwrap();
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 1);
+        assert_eq!(reader.event_offset(), Some(offsets[1]));
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 2);
+        assert_eq!(reader.event_offset(), Some(offsets[2]));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_indexed_reader() {
+        use index::{EventIndex, IndexedReader};
+
+        let buf = sample_events(3).await;
+        let mut reader =
+            IndexedReader::new(std::io::Cursor::new(buf)).await.unwrap();
+        assert_eq!(reader.len(), 3);
+        let event = reader.event(1).await.unwrap();
+        assert_eq!(event.number, 1);
+        let event = reader.event(0).await.unwrap();
+        assert_eq!(event.number, 0);
+        let event = reader.event(3).await;
+        assert!(event.is_err());
+
+        let mut sidecar = Vec::new();
+        reader.index().write_to(&mut sidecar).unwrap();
+        let index = EventIndex::read_from(sidecar.as_slice()).unwrap();
+        assert_eq!(&index, reader.index());
+
+        let buf = reader.into_inner();
+        let mut reader = IndexedReader::with_index(buf, index);
+        let event = reader.event(2).await.unwrap();
+        assert_eq!(event.number, 2);
+        let event = reader.event(0).await.unwrap();
+        assert_eq!(event.number, 0);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_with_capacity() {
+        let expected = sample_event().await;
+        let mut reader = reader::Reader::with_capacity(EVENT_TXT, 1 << 16);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event, expected);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_filter() {
+        let buf = sample_events(2).await;
+        let reader = reader::Reader::from(buf.as_slice());
+        let mut filtered = reader.filter_events(|e| e.number == 1);
+        let event = filtered.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 1);
+        assert!(!event.vertices.is_empty());
+        let next = filtered.next().await;
+        assert!(next.is_none());
+
+        let reader = reader::Reader::from(buf.as_slice());
+        let mut filtered = reader.filter_event_line(|e| {
+            assert!(e.vertices.is_empty());
+            e.number == 0
+        });
+        let event = filtered.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+        assert!(!event.vertices.is_empty());
+        let next = filtered.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_unknown_line_policy() {
+        use reader::UnknownLinePolicy;
+
+        let mut txt = EVENT_TXT.to_vec();
+        txt.extend_from_slice(b"X custom record\n");
+
+        let mut reader = reader::Reader::from(txt.as_slice());
+        let event = reader.next().await.unwrap();
+        assert!(matches!(
+            event,
+            Err(reader::LineParseError {
+                err: reader::ParseError::BadPrefix { found: 'X' },
+                ..
+            })
+        ));
+
+        let mut reader = reader::Reader::from(txt.as_slice());
+        reader.set_unknown_line_policy(UnknownLinePolicy::Skip);
+        let event = reader.next().await.unwrap().unwrap();
+        assert!(event.raw_extra.is_empty());
+
+        let mut reader = reader::Reader::from(txt.as_slice());
+        reader.set_unknown_line_policy(UnknownLinePolicy::Keep);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.raw_extra, ["X custom record"]);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains("\nX custom record\n"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_trailing_token_policy() {
+        use reader::TrailingTokenPolicy;
+
+        let expected = sample_event().await;
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let mut first_particle = true;
+        let txt: String = txt
+            .lines()
+            .flat_map(|l| {
+                let extra = if l.starts_with('P') && first_particle {
+                    first_particle = false;
+                    " 1.5e+00 -2"
+                } else if l.starts_with('E') {
+                    " 0"
+                } else {
+                    ""
+                };
+                [l, extra, "\n"]
+            })
+            .collect();
+
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        assert_eq!(reader.trailing_token_policy(), TrailingTokenPolicy::Ignore);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event, expected);
+
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        reader.set_trailing_token_policy(TrailingTokenPolicy::Error);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(err.line.starts_with('E'));
+        assert!(
+            matches!(err.err, reader::ParseError::TrailingTokens(t) if t == "0")
+        );
+
+        let txt = txt.replacen(" 0\n", "\n", 1);
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        reader.set_trailing_token_policy(TrailingTokenPolicy::Error);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(err.line.starts_with('P'));
+        assert!(matches!(
+            err.err,
+            reader::ParseError::TrailingTokens(t) if t == "1.5e+00 -2"
+        ));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_crlf() {
+        let expected = sample_event().await;
+        let txt = std::str::from_utf8(EVENT_TXT)
+            .unwrap()
+            .replace('\n', "\r\n");
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event, expected);
+        assert_eq!(reader.version(), Some("2.06.09"));
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let event = reader::parse_event(&txt).unwrap();
+        assert_eq!(event, expected);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_bytes_read() {
+        let mut reader = reader::Reader::from(EVENT_TXT);
+        assert_eq!(reader.bytes_read(), 0);
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        let next = reader.next().await;
+        assert!(next.is_none());
+        assert_eq!(reader.bytes_read(), EVENT_TXT.len() as u64);
+
+        let buf = sample_events(3).await;
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let mut last = 0;
+        let mut next = reader.next().await;
+        while let Some(event) = next {
+            assert!(event.is_ok());
+            assert!(reader.bytes_read() > last);
+            last = reader.bytes_read();
+            next = reader.next().await;
+        }
+        assert_eq!(reader.bytes_read(), buf.len() as u64);
+    }
+
+    #[test]
+    fn tst_bad_prefix_msg() {
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let txt = format!("{txt}X custom record\n");
+        let err = reader::parse_event(&txt).unwrap_err();
+        assert!(matches!(
+            err.err,
+            reader::ParseError::BadPrefix { found: 'X' }
+        ));
+        let msg = err.to_string();
+        assert!(msg.contains("Unrecognized record prefix 'X'"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_truncated() {
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let cut = txt.find("P 31 21").unwrap() + 20;
+        let truncated = &txt[..cut];
+        let nlines = truncated.lines().count();
+        let mut reader = reader::Reader::from(truncated.as_bytes());
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(err.err, reader::ParseError::UnexpectedEof));
+        assert_eq!(err.line_nr, nlines);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_truncated_at_line_end() {
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        // cut after a missing particle and after a missing vertex
+        for next in ["P 32 21", "V -22"] {
+            let truncated = &txt[..txt.find(next).unwrap()];
+            let nlines = truncated.lines().count();
+            let mut reader = reader::Reader::from(truncated.as_bytes());
+            let err = reader.next().await.unwrap().unwrap_err();
+            assert!(matches!(err.err, reader::ParseError::UnexpectedEof));
+            assert_eq!(err.line_nr, nlines);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_json_round_trip() {
+        let expected = sample_event().await;
+
+        let mut json = Vec::new();
+        let mut writer = json::JsonWriter::new(&mut json);
+        writer.write(&expected).await.unwrap();
+        writer.write(&expected).await.unwrap();
+        writer.finish().await.unwrap();
+        assert_eq!(json.iter().filter(|&&c| c == b'\n').count(), 2);
+
+        let mut reader = json::JsonReader::new(json.as_slice());
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        for _ in 0..2 {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(event, expected);
+            writer.write(&event).await.unwrap();
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+        writer.finish().await.unwrap();
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for _ in 0..2 {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(event, expected);
+        }
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_hepmc3() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        let mut writer = hepmc3::HepMC3Writer::new(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let out = std::str::from_utf8(&buf).unwrap();
+        let mut lines = out.lines();
+        assert!(lines.next().unwrap().starts_with("HepMC::Version 3."));
+        assert_eq!(lines.next().unwrap(), "HepMC::Asciiv3-START_EVENT_LISTING");
+        assert_eq!(
+            lines.next_back().unwrap(),
+            "HepMC::Asciiv3-END_EVENT_LISTING"
+        );
+        let mut declared = None;
+        let mut nparticles = 0;
+        let mut nvertices = 0;
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            match fields.next().unwrap() {
+                "E" => {
+                    let counts: Vec<usize> =
+                        fields.skip(1).map(|f| f.parse().unwrap()).collect();
+                    declared = Some((counts[0], counts[1]));
+                }
+                "U" | "W" | "T" => {}
+                "A" => assert_eq!(nparticles + nvertices, 0),
+                "P" => {
+                    nparticles += 1;
+                    let id: usize = fields.next().unwrap().parse().unwrap();
+                    assert_eq!(id, nparticles);
+                    let parent: i64 = fields.next().unwrap().parse().unwrap();
+                    assert!(parent <= 0 && parent >= -(nvertices as i64));
+                    assert_eq!(fields.count(), 7);
+                }
+                "V" => {
+                    nvertices += 1;
+                    let id: i64 = fields.next().unwrap().parse().unwrap();
+                    assert_eq!(id, -(nvertices as i64));
+                    let _status: i32 = fields.next().unwrap().parse().unwrap();
+                    let incoming = fields.next().unwrap();
+                    let incoming = incoming
+                        .strip_prefix('[')
+                        .and_then(|s| s.strip_suffix(']'))
+                        .unwrap();
+                    for id in incoming.split(',').filter(|s| !s.is_empty()) {
+                        let id: usize = id.parse().unwrap();
+                        assert!(id >= 1);
+                    }
+                    if let Some(at) = fields.next() {
+                        assert_eq!(at, "@");
+                        assert_eq!(fields.count(), 4);
+                    }
+                }
+                prefix => panic!("Unexpected HepMC3 record prefix {prefix}"),
+            }
+        }
+        assert_eq!(declared, Some((nvertices, nparticles)));
+        assert_eq!(nvertices, event.vertices.len());
+        assert_eq!(nparticles, event.particles().count());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_lhef() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        lhef::write_event(&mut buf, &event).unwrap();
+
+        let out = std::str::from_utf8(&buf).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("<event>"));
+        assert_eq!(lines.next_back(), Some("</event>"));
+        let header: Vec<_> = lines.next().unwrap().split_whitespace().collect();
+        assert_eq!(header.len(), 6);
+        assert_eq!(header[1], event.signal_process_id.to_string());
+        let nparticles: usize = header[0].parse().unwrap();
+        let expected = event
+            .particles()
+            .filter(|p| p.is_beam() || p.is_final())
+            .count();
+        assert_eq!(nparticles, expected);
+        let particles: Vec<Vec<_>> = lines
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(particles.len(), nparticles);
+        assert!(particles.iter().all(|p| p.len() == 13));
+        let (beams, outgoing) = particles.split_at(2);
+        assert!(beams.iter().all(|p| p[..4] == ["2212", "-1", "0", "0"]));
+        assert!(outgoing.iter().all(|p| p[1..4] == ["1", "1", "2"]));
+        let antiquark = particles.iter().find(|p| p[0] == "-2").unwrap();
+        assert_eq!(antiquark[4..6], ["0", "503"]);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_write_all() {
+        let first = sample_event().await;
+        let mut second = first.clone();
+        second.number = 1;
+        let events = [first, second];
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        let written = writer.write_all(&events).await.unwrap();
+        assert_eq!(written, 2);
+        writer.finish().await.unwrap();
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for expected in &events {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(&event, expected);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_writer_builder() {
+        let event = sample_event().await;
+        let mut expected = Vec::new();
+        let mut writer = writer::Writer::new(&mut expected).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let mut buf = Vec::new();
+        let builder = writer::WriterBuilder::new();
+        let mut writer = builder.build(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        assert_eq!(buf, expected);
+
+        let mut buf = Vec::new();
+        let builder = writer::WriterBuilder::new().write_footer(false);
+        let mut writer = builder.build(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.starts_with("HepMC::Version"));
+        assert!(!out.contains("END_EVENT_LISTING"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_without_footer() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        {
+            let mut writer =
+                writer::Writer::without_footer(&mut buf).await.unwrap();
+            writer.write(&event).await.unwrap();
+        }
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.starts_with("HepMC::Version"));
+        assert!(!out.contains("END_EVENT_LISTING"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_appending() {
+        let mut event = sample_event().await;
+        let mut buf = Vec::new();
+        let mut writer =
+            writer::Writer::without_footer(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        event.number = 1;
+        let mut writer = writer::Writer::appending(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let out = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(out.matches("HepMC::Version").count(), 1);
+        assert_eq!(out.matches("START_EVENT_LISTING").count(), 1);
+        assert_eq!(out.matches("END_EVENT_LISTING").count(), 1);
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for number in 0..2 {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(event.number, number);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[cfg(all(feature = "rayon", feature = "sync"))]
+    #[test]
+    fn tst_par_events() {
+        use rayon::iter::ParallelIterator;
+        use std::io::Cursor;
+
+        let buf = sample_events(20);
+        let reader = index::IndexedReader::new(Cursor::new(&buf)).unwrap();
+        let mut events: Vec<_> = reader
+            .index()
+            .par_events(|| Ok(Cursor::new(&buf)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        events.sort_by_key(|event| event.number);
+        let expected: Vec<_> = reader::Reader::from(buf.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events, expected);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_read_into() {
+        let full = sample_event().await;
+        let mut small = full.clone();
+        small.number = 1;
+        small.vertices.truncate(2);
+        small.vertices[0].particles_out.clear();
+        for particle in small.particles_mut() {
+            particle.flows.clear();
+        }
+        small.weights.clear();
+        small.weight_names.clear();
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        let events = [full.clone(), small, full];
+        writer.write_all(&events).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let mut reused = reader::Reader::from(buf.as_slice());
+        let mut event = Event::default();
+        loop {
+            let read = reused.read_into(&mut event).await.unwrap();
+            let Some(expected) = reader.next().await else {
+                assert!(!read);
+                break;
+            };
+            assert!(read);
+            assert_eq!(event, expected.unwrap());
+        }
+        assert_eq!(event, events[2]);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_intern_weight_names() {
+        let buf = sample_events(3).await;
+        let mut owned = reader::Reader::from(buf.as_slice());
+        let mut interned = reader::Reader::from(buf.as_slice());
+        interned.set_intern_weight_names(true);
+        let mut shared = Vec::new();
+        for _ in 0..3 {
+            let expected = owned.next().await.unwrap().unwrap();
+            let event = interned.next().await.unwrap().unwrap();
+            assert_eq!(event, expected);
+            shared.push(event.weight_names);
+        }
+        assert!(shared[0].ptr_eq(&shared[2]));
+    }
+
+    #[test]
+    fn tst_parse_float() {
+        const FLOATS: [&str; 17] = [
+            "5.5606031127834702e-09",
+            "-5.5606031127834702E-09",
+            "0",
+            "-0",
+            "-0.0",
+            "-0.0000000000000000e+00",
+            "1",
+            "0.1",
+            "1e5",
+            "-3.0000000000000004",
+            "9007199254740993",
+            "2.2250738585072014e-308",
+            "4.9406564584124654e-324",
+            "1.7976931348623157e+308",
+            "6.9999999371178146e+03",
+            "1e-400",
+            "inf",
+        ];
+        let line = format!("E 0 0 0 0 0 0 0 0 0 0 0 {}", FLOATS.len());
+        let line = FLOATS
+            .iter()
+            .fold(line, |line, float| format!("{line} {float}"));
+        let event = reader::parse_event(&line).unwrap();
+        for (weight, float) in event.weights.iter().zip(FLOATS) {
+            let expected: f64 = float.parse().unwrap();
+            assert_eq!(weight.to_bits(), expected.to_bits(), "{float}");
+        }
+    }
+
+    #[cfg(all(feature = "mmap", feature = "sync"))]
+    #[test]
+    fn tst_mmap() {
+        let buf = sample_events(3);
+        let name = format!("hepmc2-tst-mmap-{}.hepmc2", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, &buf).unwrap();
+        let reader = unsafe { reader::Reader::mmap(&path) }.unwrap();
+        let events: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected: Vec<_> = reader::Reader::from(buf.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events, expected);
+    }
+
+    #[cfg(all(feature = "mmap", feature = "sync"))]
+    #[test]
+    fn tst_mmap_truncated() {
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let truncated = &txt[..txt.find("P 32 21").unwrap()];
+        let name =
+            format!("hepmc2-tst-mmap-trunc-{}.hepmc2", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, truncated).unwrap();
+        let mut reader = unsafe { reader::Reader::mmap(&path) }.unwrap();
+        let err = reader.next().unwrap().unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err.err, reader::ParseError::UnexpectedEof));
+        assert_eq!(err.line_nr, truncated.lines().count());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_indexed_reader_double_ended() {
+        use index::IndexedReader;
+
+        let buf = sample_events(2).await;
+        let mut reader =
+            IndexedReader::new(std::io::Cursor::new(buf)).await.unwrap();
+        let event = reader.next_back().await.unwrap().unwrap();
+        assert_eq!(event.number, 1);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.number, 0);
+        let next = reader.next().await;
+        assert!(next.is_none());
+        let next = reader.next_back().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_line_number() {
+        let buf = sample_events(2).await;
+        let txt = std::str::from_utf8(&buf).unwrap();
+        let event_lines: Vec<_> = txt
+            .lines()
+            .zip(1..)
+            .filter(|(line, _)| line.starts_with('E'))
+            .map(|(_, line_nr)| line_nr)
+            .collect();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        assert_eq!(reader.line_number(), 0);
+        reader.next().await.unwrap().unwrap();
+        assert_eq!(reader.line_number(), event_lines[1]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn tst_json_schema() {
+        let json = json::event_to_json(&event::Event::default()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let fields: Vec<_> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        let mut expected = [
+            "number",
+            "mpi",
+            "scale",
+            "alpha_qcd",
+            "alpha_qed",
+            "signal_process_id",
+            "signal_process_vertex",
+            "beam_barcodes",
+            "random_states",
+            "weights",
+            "weight_names",
+            "vertices",
+            "xs",
+            "pdf_info",
+            "energy_unit",
+            "length_unit",
+            "units_declared",
+            "heavy_ion_info",
+            "raw_extra",
+        ];
+        expected.sort();
+        assert_eq!(fields, expected);
+
+        let p = event::FourVector::txyz(1., 2., 3., 4.);
+        let json = serde_json::to_string(&p).unwrap();
+        assert_eq!(json, "[1.0,2.0,3.0,4.0]");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn tst_json_exact_floats() {
+        let values = [
+            0.1 + 0.2,
+            1. / 3.,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::MAX,
+            -0.,
+            -1.2345678901234567e-89,
+        ];
+        let mut event = event::Event::default();
+        let mut vertex = event::Vertex::default();
+        for value in values {
+            vertex.particles_out.push(event::Particle {
+                p: event::FourVector::txyz(value, -value, value, value),
+                m: value,
+                ..Default::default()
+            });
+        }
+        event.vertices.push(vertex);
+        event.weights = values.to_vec();
+
+        let json = json::event_to_json(&event).unwrap();
+        let read = json::event_from_json(&json).unwrap();
+        let bits = |event: &event::Event| -> Vec<u64> {
+            event
+                .particles()
+                .flat_map(|p| p.p.0.into_iter().chain([p.m]))
+                .chain(event.weights.iter().copied())
+                .map(f64::to_bits)
+                .collect()
+        };
+        assert_eq!(bits(&read), bits(&event));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn tst_json_non_finite() {
+        let mut event = event::Event {
+            number: 3,
+            ..Default::default()
+        };
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            event.scale = value;
+            let err = json::event_to_json(&event).unwrap_err();
+            assert!(matches!(err, json::JsonError::NonFinite(3)));
+        }
+        event.scale = 0.;
+        event.vertices.push(event::Vertex {
+            particles_in: vec![event::Particle {
+                theta: f64::NAN,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let err = json::event_to_json(&event).unwrap_err();
+        assert!(matches!(err, json::JsonError::NonFinite(3)));
+    }
+
+    #[cfg(feature = "json")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_json_writer_non_finite() {
+        let event = event::Event {
+            alpha_qcd: f64::NAN,
+            ..Default::default()
+        };
+        let mut json = Vec::new();
+        let mut writer = json::JsonWriter::new(&mut json);
+        let err = writer.write(&event).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        writer.finish().await.unwrap();
+        assert!(json.is_empty());
+    }
+
+    #[cfg(feature = "csv")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_csv() {
+        let event = sample_event().await;
+        let mut output = Vec::new();
+        csv::write_particles_csv([&event], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, PARTICLES_CSV);
+
+        let options = csv::CsvOptions::new().delimiter(';').all_particles(true);
+        let mut output = Vec::new();
+        csv::write_particles_csv_with([&event, &event], &mut output, &options)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next(),
+            Some("event_number;pdg_id;status;px;py;pz;e;m")
+        );
+        assert_eq!(lines.count(), 2 * event.particles().count());
+        assert!(output.contains("\n0;2212;4;0.0;0.0;6999.999937117815;7000.0;"));
+    }
+
+    #[cfg(feature = "csv")]
+    const PARTICLES_CSV: &str = "\
+event_number,pdg_id,status,px,py,pz,e,m
+0,-2,1,2.5237885213344935,3.3116569526639856,-69.14314149775808,69.26839536506921,0.0
+0,21,1,1.6780099262880444,-0.01258571588949442,1.561858922116857,2.292439531535948,0.0
+0,21,1,0.7015436852248482,-1.673170474700709,-2.744081019808675,3.2896266692386575,0.0
+0,21,1,-1.5006136258617786,-1.1451114544473862,-211.83620872426775,211.84461864340338,0.0
+0,21,1,1.307180544524667,0.12990482014036053,-36.173394193995875,36.19723806801562,0.0
+0,2,1,-0.9834513292440703,2.690421552193925,-1321.6456472221134,1321.6487515091915,0.0
+0,21,1,-0.9800167457297735,1.3322582893034975,-614.6441297156254,614.6463548568801,0.0
+0,21,1,-0.6700938500184943,-0.3903008019701275,-84.88783092929236,84.89137294427485,0.0
+0,21,1,3.631300879389308,-0.9682072466926734,-323.1693366306664,323.19118785891123,0.0
+0,-13,1,6.728063585279312,26.135691981490424,35.75711258732996,44.79869974935709,0.10565837
+0,14,1,-12.435711898718612,-29.410557911101773,-17.410492121416357,36.36967257180224,0.0
+";
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_drop_silent() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        {
+            let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+            writer.set_on_unfinished(writer::OnUnfinished::Silent);
+            writer.write(&event).await.unwrap();
+        }
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let next = reader.next().await;
+        assert_eq!(next.unwrap().unwrap(), event);
+        let next = reader.next().await;
+        assert!(next.is_none());
+        assert!(buf.ends_with(b"HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn tst_drop_panic() {
+        let mut buf = Vec::new();
+        let write = || {
+            let mut writer = writer::WriterBuilder::new()
+                .on_unfinished(writer::OnUnfinished::Panic)
+                .build(&mut buf)
+                .unwrap();
+            writer.write(event::Event::default()).unwrap();
+        };
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(write));
+        assert!(res.is_err());
+        assert!(buf.ends_with(b"HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+    }
+
+    #[cfg(all(feature = "sync", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "dropped before finished")]
+    fn tst_strict_drop() {
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::strict(&mut buf).unwrap();
+        writer.write(event::Event::default()).unwrap();
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn tst_strict_finish() {
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::strict(&mut buf).unwrap();
+        writer.write(event::Event::default()).unwrap();
+        writer.finish().unwrap();
+        assert!(buf.ends_with(b"HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_process_accumulator() {
+        let buf = sample_events(2).await;
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let mut acc = stats::ProcessAccumulator::new();
+        let mut next = reader.next().await;
+        let mut weight = 0.;
+        while let Some(event) = next {
+            let event = event.unwrap();
+            weight = event.weights[0];
+            acc.add(&event);
+            next = reader.next().await;
+        }
+        let stats = acc.get(0).unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.sum_weights, 2. * weight);
+        assert_eq!(stats.sum_weights_sq, 2. * weight * weight);
+
+        let event = event::Event {
+            signal_process_id: 5,
+            weights: vec![-3.],
+            ..Default::default()
+        };
+        acc.extend([&event, &event::Event::default()]);
+        let stats: Vec<_> = acc.into_iter().collect();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].0, 0);
+        assert_eq!(stats[0].1.count, 3);
+        assert_eq!(stats[0].1.sum_weights, 2. * weight + 1.);
+        assert_eq!(stats[1].0, 5);
+        assert_eq!(
+            stats[1].1,
+            stats::ProcessStats {
+                count: 1,
+                sum_weights: -3.,
+                sum_weights_sq: 9.
+            }
+        );
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_statistics() {
+        let event = sample_event().await;
+        let buf = sample_events(2).await;
+        let reader = reader::Reader::from(buf.as_slice());
+        let stats = reader.statistics().await.unwrap();
+        let weight = event.weights[0];
+        assert_eq!(stats.n_events, 2);
+        assert_eq!(stats.sum_weights, 2. * weight);
+        assert_eq!(stats.sum_weights_sq, 2. * weight * weight);
+        assert_eq!(stats.cross_section, Some(event.xs));
+        assert_eq!(stats.mean_weight(), Some(weight));
+        assert_eq!(stats.weight_error(), Some(0.));
+
+        let mut stats = stats::Statistics::new();
+        assert_eq!(stats.mean_weight(), None);
+        let event = event::Event {
+            weights: vec![3.],
+            ..Default::default()
+        };
+        stats.extend([&event::Event::default(), &event]);
+        assert_eq!(stats.mean_weight(), Some(2.));
+        assert_eq!(stats.weight_error(), Some(1.));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tst_stream() {
+        use futures::StreamExt;
+
+        let buf = sample_events(3).await;
+        let reader = reader::Reader::from(buf.as_slice());
+        let events: Vec<_> = reader.collect().await;
+        assert_eq!(events.len(), 3);
+        for (event, number) in events.into_iter().zip(0..) {
+            assert_eq!(event.unwrap().number, number);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tst_stream_resume() {
+        use futures::StreamExt;
+
+        let mut buf = sample_events(3).await;
+        // malformed event, to test resuming while skipping it
+        let pos = buf.windows(3).position(|w| w == b"\nE ").unwrap() + 1;
+        buf.splice(pos..pos, b"E 1 x\nV -1 0 0 0 0 0 0 1 0\n".to_vec());
+
+        let mut expected = Vec::new();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        reader.set_error_policy(reader::ErrorPolicy::SkipEvent);
+        while let Some(event) = reader.next().await {
+            expected.push(event.unwrap());
+        }
+        assert_eq!(expected.len(), 3);
+
+        // return pending between small chunks
+        let mut mock = tokio_test::io::Builder::new();
+        for chunk in buf.chunks(64) {
+            mock.read(chunk).wait(std::time::Duration::ZERO);
+        }
+        let input = tokio::io::BufReader::new(mock.build());
+        let mut reader = reader::Reader::from(input);
+        reader.set_error_policy(reader::ErrorPolicy::SkipEvent);
+        let events: Vec<_> = (&mut reader).map(Result::unwrap).collect().await;
+        assert_eq!(events, expected);
+        assert_eq!(reader.errors().len(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tst_sink() {
+        use futures::{SinkExt, StreamExt};
+
+        let input = sample_events(3).await;
+        let reader = reader::Reader::from(input.as_slice());
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        reader
+            .map(|event| event.map_err(std::io::Error::other))
+            .forward(&mut writer)
+            .await
+            .unwrap();
+        drop(writer);
+        assert!(buf.ends_with(b"HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+
+        let reader = reader::Reader::from(buf.as_slice());
+        let events: Vec<_> = reader.map(Result::unwrap).collect().await;
+        let expected: Vec<_> = reader::Reader::from(input.as_slice())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events, expected);
+
+        // sending references
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.send(&events[0]).await.unwrap();
+        writer.feed(&events[1]).await.unwrap();
+        SinkExt::<&event::Event>::close(&mut writer).await.unwrap();
+        drop(writer);
+        let reader = reader::Reader::from(buf.as_slice());
+        let read: Vec<_> = reader.map(Result::unwrap).collect().await;
+        assert_eq!(read, events[..2]);
+
+        // partial writes and pending output
+        let mut mock = tokio_test::io::Builder::new();
+        for chunk in buf.chunks(50) {
+            mock.write(chunk).wait(std::time::Duration::ZERO);
+        }
+        let mut writer = writer::Writer::new(mock.build()).await.unwrap();
+        futures::stream::iter(&events[..2])
+            .map(Ok)
+            .forward(&mut writer)
+            .await
+            .unwrap();
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_no_vertices() {
+        let input = b"E 1 -1 0 0 0 0 0 0 0 0 0 1 2.5\nU GEV MM\nC 3.5 0.5\n";
+        let mut reader = reader::Reader::from(input.as_slice());
+        let event = reader.next().await.unwrap().unwrap();
+        assert!(event.vertices.is_empty());
+        assert_eq!(event.weights, [2.5]);
+        assert_eq!(event.xs.cross_section, 3.5);
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let events = [
+            event,
+            event::Event {
+                number: 2,
+                ..Default::default()
+            },
+        ];
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write_all(&events).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for expected in &events {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(&event, expected);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_event_builder() {
+        let vertex = event::Vertex {
+            barcode: -1,
+            particles_out: vec![event::Particle {
+                barcode: 1,
+                id: 22,
+                p: event::FourVector::txyz(10., 0., 0., 10.),
+                status: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let xs = event::CrossSection {
+            cross_section: 2.5,
+            cross_section_error: 0.5,
+        };
+        let event = event::Event::new(7)
+            .with_vertex(vertex.clone())
+            .with_weight(1.5)
+            .with_weight(-0.5)
+            .with_cross_section(xs);
+        assert_eq!(event.number, 7);
+        assert_eq!(event.vertices, [vertex]);
+        assert_eq!(event.weights, [1.5, -0.5]);
+        assert_eq!(event.xs, xs);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read, event);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_particle_new() {
+        let p = event::FourVector::txyz(50., 3., -4., 0.5);
+        let particle = event::Particle::new(-11, p, 1);
+        let mut vertex = event::Vertex::new(-1, 2);
+        assert!(vertex.particles_in.is_empty());
+        assert!(vertex.particles_out.is_empty());
+        vertex.particles_out.push(particle);
+        let event = event::Event::new(1).with_vertex(vertex);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read.vertices[0].barcode, -1);
+        assert_eq!(read.vertices[0].status, 2);
+        let read: Vec<_> = read.particles().collect();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].id, -11);
+        assert_eq!(read[0].p, p);
+        assert_eq!(read[0].status, 1);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_observables() {
+        let event = sample_event().await;
+        assert!((event.scalar_ht() - 78.82878081973516).abs() < 1e-10);
+
+        // the only invisible particle is a neutrino
+        let met = event.missing_et();
+        let expected = if cfg!(feature = "pdg") {
+            let neutrino = event.final_state().find(|p| p.id == 14).unwrap();
+            neutrino.p.0
+        } else {
+            [0.; 4]
+        };
+        assert!((met.e() - expected[1].hypot(expected[2])).abs() < 1e-6);
+        assert!((met.px() - expected[1]).abs() < 1e-6);
+        assert!((met.py() - expected[2]).abs() < 1e-6);
+        assert_eq!(met.pz(), 0.);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_concatenated_listings() {
+        let mut expected = sample_event().await;
+        let mut buf = sample_events(2).await;
+        let second = sample_events(3).await;
+        let second = String::from_utf8(second).unwrap();
+        let second = second.replace("2.06.09", "2.06.10");
+        buf.extend_from_slice(second.as_bytes());
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for (number, version) in [
+            (0, "2.06.09"),
+            (1, "2.06.09"),
+            (0, "2.06.10"),
+            (1, "2.06.10"),
+            (2, "2.06.10"),
+        ] {
+            let event = reader.next().await.unwrap().unwrap();
+            expected.number = number;
+            assert_eq!(event, expected);
+            assert_eq!(reader.version(), Some(version));
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let skipped = reader.skip_events(10).await.unwrap();
+        assert_eq!(skipped, 5);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_sort_vertices() {
+        let event = sample_event().await;
+        let mut shuffled = event.clone();
+        shuffled.vertices.reverse();
+        shuffled.vertices.swap(0, 2);
+        assert_ne!(shuffled, event);
+        shuffled.sort_vertices();
+        assert_eq!(shuffled, event);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write(&shuffled).await.unwrap();
+        writer.finish().await.unwrap();
+        let vertex_lines = |txt: &[u8]| -> Vec<String> {
+            let txt = std::str::from_utf8(txt).unwrap();
+            txt.lines()
+                .filter(|l| l.starts_with('V'))
+                .map(|l| l.split(' ').nth(1).unwrap().to_owned())
+                .collect()
+        };
+        let barcodes = vertex_lines(&buf);
+        assert_eq!(barcodes, vertex_lines(EVENT_TXT));
+        assert_eq!(barcodes[..3], ["-1", "-2", "-3"]);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_color_flow() {
+        let event = sample_event().await;
+        let mut gluon = event.particle_by_barcode(9).unwrap().clone();
+        assert_eq!(gluon.id, 21);
+        assert_eq!(gluon.color(), Some(502));
+        assert_eq!(gluon.anticolor(), Some(501));
+
+        gluon.flows.insert(3, 505);
+        gluon.set_color(Some(503));
+        gluon.set_anticolor(None);
+        assert_eq!(gluon.color(), Some(503));
+        assert_eq!(gluon.anticolor(), None);
+        assert_eq!(gluon.flows, [(1, 503), (3, 505)].into());
+
+        let muon = event.particles().find(|p| p.id == -13).unwrap();
+        assert_eq!(muon.color(), None);
+        assert_eq!(muon.anticolor(), None);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_hepmc_precision() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        let mut writer = writer::WriterBuilder::new()
+            .precision(writer::HEPMC_PRECISION)
+            .build(&mut buf)
+            .await
+            .unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        // the sample event has no pdf info, so compare all other records
+        let records = |txt: &[u8]| -> Vec<String> {
+            let txt = std::str::from_utf8(txt).unwrap();
+            txt.lines()
+                .filter(|l| !l.is_empty() && !l.starts_with(['H', 'F']))
+                .map(str::to_owned)
+                .collect()
+        };
+        let expected = records(EVENT_TXT);
+        assert_eq!(expected.len(), 62);
+        assert_eq!(records(&buf), expected);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_always_write_weight_names() {
+        let events = [
+            event::Event::new(1).with_weight(0.5).with_weight(2.),
+            event::Event::new(2),
+        ];
+        let mut buf = Vec::new();
+        let mut writer = writer::WriterBuilder::new()
+            .always_write_weight_names(true)
+            .build(&mut buf)
+            .await
+            .unwrap();
+        assert!(writer.always_write_weight_names());
+        writer.write_all(&events).await.unwrap();
+        writer.finish().await.unwrap();
+        let txt = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(txt.lines().filter(|l| *l == "N 0").count(), 2);
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for expected in &events {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(&event, expected);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        reader.set_intern_weight_names(true);
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.weights, [0.5, 2.]);
+        assert!(event.weight_names.is_empty());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_pdf_info_without_ids() {
+        const PDF_LINE: &str = "F 21 -2 0.25 0.5 91.2 1.5 0.75";
+        let input = format!("E 1 -1 0 0 0 0 0 0 0 0 0 0\n{PDF_LINE}\n");
+        let mut reader = reader::Reader::from(input.as_bytes());
+        let mut event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.pdf_info.parton_id, [21, -2]);
+        assert_eq!(event.pdf_info.xf, [1.5, 0.75]);
+        assert_eq!(event.pdf_info.pdf_id, [0, 0]);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::WriterBuilder::new()
+            .omit_zero_pdf_ids(true)
+            .build(&mut buf)
+            .await
+            .unwrap();
+        assert!(writer.omit_zero_pdf_ids());
+        writer.write(&event).await.unwrap();
+        event.pdf_info.pdf_id = [0, 10042];
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let pdf_lines: Vec<_> =
+            out.lines().filter(|l| l.starts_with('F')).collect();
+        assert_eq!(pdf_lines, [PDF_LINE, &format!("{PDF_LINE} 0 10042")]);
+
+        event.pdf_info.pdf_id = [0, 0];
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains(&format!("\n{PDF_LINE} 0 0\n")));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_record_reader() {
+        use record::Record;
+
+        let event = sample_event().await;
+        let mut txt = EVENT_TXT.to_vec();
+        txt.extend_from_slice(b"X custom record\n");
+        let mut reader = record::RecordReader::from(txt.as_slice());
+        let mut prefixes = String::new();
+        let mut vertices = Vec::new();
+        let mut particles = Vec::new();
+        let mut next = reader.next().await;
+        while let Some(record) = next {
+            let record = record.unwrap();
+            let prefix = match record {
+                Record::EventHeader(header) => {
+                    assert_eq!(header.number, event.number);
+                    assert_eq!(header.weights, event.weights);
+                    assert!(header.vertices.is_empty());
+                    'E'
+                }
+                Record::Vertex(vertex) => {
+                    vertices.push(vertex);
+                    'V'
+                }
+                Record::Particle(particle) => {
+                    particles.push(particle);
+                    'P'
+                }
+                Record::Units(energy, length) => {
+                    assert_eq!(energy, event.energy_unit);
+                    assert_eq!(length, event.length_unit);
+                    'U'
+                }
+                Record::CrossSection(xs) => {
+                    assert_eq!(xs, event.xs);
+                    'C'
+                }
+                Record::PdfInfo(pdf_info) => {
+                    assert_eq!(pdf_info, event.pdf_info);
+                    'F'
+                }
+                Record::HeavyIon(_) => 'H',
+                Record::WeightNames(names) => {
+                    assert_eq!(names, *event.weight_names);
+                    'N'
+                }
+                Record::Unknown(line) => {
+                    assert_eq!(line, "X custom record");
+                    'X'
+                }
+            };
+            prefixes.push(prefix);
+            next = reader.next().await;
+        }
+
+        let txt = std::str::from_utf8(EVENT_TXT).unwrap();
+        let expected: String = txt
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with("HepMC"))
+            .map(|l| l.chars().next().unwrap())
+            .chain(['X'])
+            .collect();
+        assert_eq!(prefixes, expected);
+        assert_eq!(reader.line_number(), txt.lines().count() + 1);
+        let expected: Vec<_> = event.particles().cloned().collect();
+        assert_eq!(particles, expected);
+        assert_eq!(vertices.len(), event.vertices.len());
+        for (vertex, expected) in vertices.iter().zip(&event.vertices) {
+            assert_eq!(vertex.barcode, expected.barcode);
+            assert_eq!(vertex.status, expected.status);
+            assert!(vertex.particles_out.is_empty());
+        }
+
+        let mut reader = record::RecordReader::from(b"V 1 0\n".as_slice());
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert_eq!(err.line_nr, 1);
+    }
+
+    #[maybe_async::maybe_async]
+    async fn write_events<I>(events: I) -> Vec<u8>
+    where
+        I: IntoIterator,
+        I::Item: std::borrow::Borrow<event::Event>,
+    {
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        writer.write_all(events).await.unwrap();
+        writer.finish().await.unwrap();
+        buf
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_write_owned_and_borrowed() {
+        let event = sample_event().await;
+        let expected = sample_events(2).await;
+        let events: Vec<_> = (0..2)
+            .map(|number| event::Event {
+                number,
+                ..event.clone()
+            })
+            .collect();
+        let borrowed: Vec<&event::Event> = events.iter().collect();
+        let buf = write_events(borrowed).await;
+        assert_eq!(buf, expected);
+        let buf = write_events(events.clone()).await;
+        assert_eq!(buf, expected);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::new(&mut buf).await.unwrap();
+        let [first, second] = <[event::Event; 2]>::try_from(events).unwrap();
+        writer.write(&first).await.unwrap();
+        writer.write(second).await.unwrap();
+        writer.finish().await.unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_duplicate_vertices() {
+        let input = b"E 1 -1 0 0 0 0 0 2 0 0 0 0
+V -1 0 0 0 0 0 0 1 0
+P 1 22 0 0 1 1 0 1 0 0 0 0
+V -1 0 0 0 0 0 0 1 0
+P 2 22 0 0 -1 1 0 1 0 0 0 0
+";
+        let mut reader = reader::Reader::from(input.as_slice());
+        assert!(!reader.detect_duplicate_vertices());
+        let event = reader.next().await.unwrap().unwrap();
+        assert_eq!(event.vertices.len(), 2);
+
+        let mut reader = reader::Reader::from(input.as_slice());
+        reader.set_detect_duplicate_vertices(true);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(err.err, reader::ParseError::DuplicateVertex(-1)));
+        assert_eq!(err.line_nr, 4);
+
+        let mut buf = sample_events(2).await;
+        buf.extend_from_slice(input);
+        let mut reader = reader::Reader::from(buf.as_slice());
+        reader.set_detect_duplicate_vertices(true);
+        for _ in 0..2 {
+            let event = reader.next().await.unwrap();
+            assert!(event.is_ok());
+        }
+        let event = reader.next().await.unwrap();
+        assert!(event.is_err());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_with_meta() {
+        let buf = sample_events(2).await;
+        let txt = std::str::from_utf8(&buf).unwrap();
+        let lines: Vec<_> = txt.lines().collect();
+        let mut reader = reader::Reader::from(buf.as_slice()).with_meta();
+        let mut metas = Vec::new();
+        let mut next = reader.next().await;
+        while let Some(res) = next {
+            let (meta, event) = res.unwrap();
+            let line = lines[meta.line_nr - 1];
+            assert!(line.starts_with(&format!("E {} ", event.number)));
+            let offset = meta.offset as usize;
+            assert!(txt[offset..].starts_with(line));
+            metas.push(meta);
+            next = reader.next().await;
+        }
+        assert_eq!(metas.len(), 2);
+        assert_eq!(metas[0].line_nr, 3);
+        assert!(metas[0].line_nr < metas[1].line_nr);
+        assert!(metas[0].offset < metas[1].offset);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_content_hash() {
+        let event = sample_event().await;
+        // move a float by a few ulps towards the centre of the range
+        // that is rounded to the same 40-bit mantissa
+        let nudge = |x: f64| -> f64 {
+            let bits = x.to_bits();
+            let low = bits & 0xfff;
+            let bits = if low < 0x800 {
+                bits - low.min(3)
+            } else {
+                bits + (0x1000 - low).min(3)
+            };
+            f64::from_bits(bits)
+        };
+        let mut copy = event.clone();
+        for particle in copy.particles_mut() {
+            for p in &mut particle.p.0 {
+                *p = nudge(*p);
+            }
+            particle.m = nudge(particle.m);
+        }
+        copy.scale = nudge(copy.scale);
+        assert_ne!(copy, event);
+        assert_eq!(event.content_hash(), copy.content_hash());
+
+        copy.particles_mut().next().unwrap().p[3] *= 1. + 1e-9;
+        assert_ne!(event.content_hash(), copy.content_hash());
+
+        let mut copy = event.clone();
+        copy.vertices[0].barcode -= 1;
+        assert_ne!(event.content_hash(), copy.content_hash());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_approx_eq() {
+        let event = sample_event().await;
+        let mut buf = Vec::new();
+        let mut writer = writer::WriterBuilder::new()
+            .precision(10)
+            .build(&mut buf)
+            .await
+            .unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_ne!(read, event);
+        assert!(read.approx_eq(&event, 1e-9, 0.));
+        assert!(!read.approx_eq(&event, 1e-15, 0.));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_chunks() {
+        let buf = sample_events(3).await;
+        let mut reader = reader::Reader::from(buf.as_slice()).chunks(2);
+        let mut sizes = Vec::new();
+        let mut numbers = Vec::new();
+        let mut next = reader.next().await;
+        while let Some(chunk) = next {
+            let chunk = chunk.unwrap();
+            sizes.push(chunk.len());
+            numbers.extend(chunk.iter().map(|event| event.number));
+            next = reader.next().await;
+        }
+        assert_eq!(sizes, [2, 1]);
+        assert_eq!(numbers, [0, 1, 2]);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_strict() {
+        let mut reader = reader::Reader::from(EVENT_TXT);
+        assert!(!reader.strict());
+        reader.set_strict(true);
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+
+        let buf = sample_events(2).await;
+        let mut reader = reader::Reader::from(buf.as_slice());
+        reader.set_strict(true);
+        let mut next = reader.next().await;
+        while let Some(event) = next {
+            assert!(event.is_ok());
+            next = reader.next().await;
+        }
+
+        let short = b"E 1 -1 0 0 0 0 0 1 0 0 0 0
+V -1 0 0 0 0
+P 1 22 0 0 1 1 0 1 0 0 0 0
+";
+        let mut reader = reader::Reader::from(short.as_slice());
+        reader.set_strict(true);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err.err,
+            reader::ParseError::FieldCount {
+                record: 'V',
+                expected: 9,
+                found: 5
+            }
+        ));
+        assert_eq!(err.line_nr, 2);
+
+        let long = b"E 1 -1 0 0 0 0 0 1 0 0 0 0
+V -1 0 0 0 0 0 0 1 0 7
+P 1 22 0 0 1 1 0 1 0 0 0 0
+";
+        let mut reader = reader::Reader::from(long.as_slice());
+        let event = reader.next().await.unwrap();
+        assert!(event.is_ok());
+        let mut reader = reader::Reader::from(long.as_slice());
+        reader.set_strict(true);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err.err,
+            reader::ParseError::FieldCount {
+                record: 'V',
+                expected: 9,
+                found: 10
+            }
+        ));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_heavy_ion_round_trip() {
+        let mut event = sample_event().await;
+        event.heavy_ion_info = Some(event::HeavyIonInfo {
+            ncoll_hard: 3,
+            npart_proj: 197,
+            npart_targ: 208,
+            ncoll: 1234,
+            spectator_neutrons: -1,
+            spectator_protons: -2,
+            n_nwounded_collisions: i32::MAX,
+            nwounded_n_collisions: i32::MIN,
+            nwounded_nwounded_collisions: 17,
+            impact_parameter: 1. / 3.,
+            event_plane_angle: -std::f64::consts::PI,
+            eccentricity: 1e-300,
+            sigma_inel_nn: 6.999_999_937_117_815e3,
+        });
+        for precision in [None, Some(writer::HEPMC_PRECISION)] {
+            let mut buf = Vec::new();
+            let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+            writer.set_precision(precision);
+            writer.write(&event).await.unwrap();
+            writer.finish().await.unwrap();
+            let mut reader = reader::Reader::from(buf.as_slice());
+            let read = reader.next().await.unwrap().unwrap();
+            assert_eq!(read.heavy_ion_info, event.heavy_ion_info);
+        }
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_wide_random_states() {
+        let line = "E 1 -1 0 0 0 0 0 0 0 0 2 4294967296 -9223372036854775808 0";
+        let event = reader::parse_event(line).unwrap();
+        assert_eq!(event.random_states, [1 << 32, i64::MIN]);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read.random_states, event.random_states);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_non_finite() {
+        let mut event = sample_event().await;
+        event.scale = f64::INFINITY;
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        assert_eq!(writer.non_finite(), writer::NonFinite::Error);
+        let res = writer.write(&event).await;
+        assert!(res.is_err());
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        let mut buf = Vec::new();
+        let mut writer = writer::WriterBuilder::new()
+            .non_finite(writer::NonFinite::Substitute)
+            .build(&mut buf)
+            .await
+            .unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read.scale, 0.);
+        event.scale = 0.;
+        assert_eq!(read, event);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_eq_unordered() {
+        let event = sample_event().await;
+        let mut reordered = event.clone();
+        for vertex in &mut reordered.vertices {
+            vertex.particles_out.reverse();
+        }
+        assert_ne!(reordered, event);
+        assert!(!reordered.approx_eq(&event, 0., 0.));
+        assert!(reordered.eq_unordered(&event, 0., 0.));
+        assert!(event.eq_unordered(&reordered, 0., 0.));
+
+        let vertex = reordered
+            .vertices
+            .iter_mut()
+            .find(|v| v.particles_out.len() > 1)
+            .unwrap();
+        vertex.particles_out[0].p[0] *= 1. + 1e-12;
+        assert!(!reordered.eq_unordered(&event, 0., 0.));
+        assert!(reordered.eq_unordered(&event, 1e-10, 0.));
+
+        // particles still have to belong to the same vertex
+        let particle = reordered.vertices[0].particles_out.remove(0);
+        reordered.vertices[1].particles_out.push(particle);
+        assert!(!reordered.eq_unordered(&event, 1e-10, 0.));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_beam_barcodes() {
+        let mut event = sample_event().await;
+        assert_eq!(event.beam_barcodes, [1, 2]);
+        event.beam_barcodes = [3, -4];
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let txt = std::str::from_utf8(&buf).unwrap();
+        let event_line = txt.lines().find(|l| l.starts_with('E')).unwrap();
+        let fields: Vec<_> = event_line.split(' ').collect();
+        assert_eq!(fields[9..11], ["3", "-4"]);
+
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read.beam_barcodes, [3, -4]);
+        assert_eq!(read, event);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_visitor() {
+        use event::{CrossSection, Particle};
+        use record::{RecordReader, Visitor};
+
+        struct Mirror;
+
+        impl Visitor for Mirror {
+            fn on_particle(&mut self, particle: &mut Particle) -> bool {
+                particle.p[3] = -particle.p[3];
+                true
+            }
+
+            fn on_cross_section(&mut self, _xs: &mut CrossSection) -> bool {
+                false
+            }
+        }
+
+        let input = sample_events(2).await;
+        let mut records = RecordReader::from(input.as_slice());
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        records.visit(&mut Mirror, &mut writer).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let mut expected = sample_event().await;
+        expected.xs = Default::default();
+        for vertex in &mut expected.vertices {
+            let particles = vertex
+                .particles_in
+                .iter_mut()
+                .chain(vertex.particles_out.iter_mut());
+            for particle in particles {
+                particle.p[3] = -particle.p[3];
+            }
+        }
+        let mut reader = reader::Reader::from(buf.as_slice());
+        for number in 0..2 {
+            expected.number = number;
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(event, expected);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+
+        struct Nan;
+
+        impl Visitor for Nan {
+            fn on_particle(&mut self, particle: &mut Particle) -> bool {
+                particle.m = f64::NAN;
+                true
+            }
+        }
+
+        let mut records = RecordReader::from(input.as_slice());
+        let mut writer = writer::Writer::try_from(Vec::new()).await.unwrap();
+        let err = records.visit(&mut Nan, &mut writer).await.unwrap_err();
+        let reader::ParseError::Io(err) = err.err else {
+            panic!("expected I/O error, got {err}");
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_units_declared() {
+        let txt = b"E 0 -1 0 0 0 0 0 0 0 0 0 0
+E 1 -1 0 0 0 0 0 0 0 0 0 0
+U MEV CM
+E 2 -1 0 0 0 0 0 0 0 0 0 0
+";
+        let mut reader = reader::Reader::from(txt.as_slice());
+        let mut event = Event::default();
+        let mut declared = Vec::new();
+        while reader.read_into(&mut event).await.unwrap() {
+            declared.push(event.units_declared);
+        }
+        assert_eq!(declared, [false, true, false]);
+        assert!(Event::default().units_declared);
+
+        let txt = "E 0 -1 0 0 0 0 0 0 0 0 0 0\n";
+        let event = reader::parse_event(txt).unwrap();
+        assert!(!event.units_declared);
+        assert_eq!(event.energy_unit, event::EnergyUnit::GEV);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let txt = std::str::from_utf8(&buf).unwrap();
+        assert!(txt.lines().any(|line| line == "U GEV CM"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_mixed_case_units() {
+        let txt = "E 0 -1 0 0 0 0 0 0 0 0 0 0\nU GeV mm\n";
+        let event = reader::parse_event(txt).unwrap();
+        assert_eq!(event.energy_unit, event::EnergyUnit::GEV);
+        assert_eq!(event.length_unit, event::LengthUnit::MM);
+
+        let mut buf = Vec::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+        let txt = std::str::from_utf8(&buf).unwrap();
+        assert!(txt.lines().any(|line| line == "U GEV MM"));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_error_column() {
+        let txt = "E 0 -1 0 0 0 0 0 1 0 0 0 0
+V -1 0 0 0 0 0 0 1 0
+P 1 22 0 0 1.5e0.3 1 0 1 0 0 0 0
+";
+        let column = txt.lines().nth(2).unwrap().find(".3").unwrap();
+
+        let mut reader = reader::Reader::from(txt.as_bytes());
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(err.err, reader::ParseError::Parse { .. }));
+        assert_eq!(err.line_nr, 3);
+        assert_eq!(err.column, Some(column));
+
+        let err = reader::parse_event(txt).unwrap_err();
+        assert_eq!(err.column, Some(column));
+
+        let err = reader::parse_event("").unwrap_err();
+        assert_eq!(err.column, None);
+    }
+
+    /// Records the number of writes to the wrapped stream
+    #[cfg(feature = "sync")]
+    struct CountWrites<W> {
+        inner: W,
+        nwrites: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    #[cfg(feature = "sync")]
+    impl<W: std::io::Write> std::io::Write for CountWrites<W> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.nwrites.set(self.nwrites.get() + 1);
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn tst_buffered_write() {
+        let mut event = sample_event();
+        let nwrites = std::rc::Rc::default();
+        let mut out = Vec::new();
+        let stream = CountWrites {
+            inner: &mut out,
+            nwrites: std::rc::Rc::clone(&nwrites),
+        };
+        let mut writer = writer::Writer::new(stream).unwrap();
+        for number in 0..3 {
+            event.number = number;
+            let nwrites_before = nwrites.get();
+            writer.write(&event).unwrap();
+            assert_eq!(nwrites.get(), nwrites_before + 1);
+        }
+        writer.finish().unwrap();
+        assert_eq!(out, sample_events(3));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn tst_threaded_writer() {
+        use threaded::ThreadedWriter;
+
+        let mut event = sample_event();
+        let mut writer = ThreadedWriter::new(Vec::new()).unwrap();
+        for number in 0..3 {
+            event.number = number;
+            writer.write(&event).unwrap();
+        }
+        let out = writer.finish().unwrap();
+        assert_eq!(out, sample_events(3));
+
+        let builder = writer::WriterBuilder::new().precision(5);
+        let mut expected = Vec::new();
+        let mut writer = builder.clone().build(&mut expected).unwrap();
+        writer.write(&event).unwrap();
+        writer.finish().unwrap();
+        let mut writer =
+            ThreadedWriter::with_builder(Vec::new(), builder).unwrap();
+        writer.write(&event).unwrap();
+        assert_eq!(writer.finish().unwrap(), expected);
+
+        struct Failing;
+
+        impl std::io::Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WriteZero.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let res = ThreadedWriter::new(Failing).and_then(|mut writer| {
+            for _ in 0..100 {
+                writer.write(&event)?;
+            }
+            writer.finish().map(|_| ())
+        });
+        let err = res.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn tst_nth() {
+        let buf = sample_events(5);
+        let mut reader = reader::Reader::from(buf.as_slice());
+        let event = reader.nth(1).unwrap().unwrap();
+        assert_eq!(event.number, 1);
+        let event = reader.nth(1).unwrap().unwrap();
+        assert_eq!(event.number, 3);
+        assert!(reader.nth(1).is_none());
+        assert!(reader.next().is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_particles() {
+        let mut event = sample_event().await;
+        let nparticles: usize = event
+            .vertices
+            .iter()
+            .map(|v| v.particles_in.len() + v.particles_out.len())
+            .sum();
+        assert_eq!(nparticles, 35);
+        assert_eq!(event.particles().count(), nparticles);
+        assert_eq!(event.particles_mut().count(), nparticles);
+
+        for particle in event.particles_mut() {
+            particle.status = -7;
+        }
+        assert!(event.particles().all(|p| p.status == -7));
+        assert!(event
+            .vertices
+            .iter()
+            .flat_map(|v| v.particles_in.iter().chain(&v.particles_out))
+            .all(|p| p.status == -7));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_final_state() {
+        let event = sample_event().await;
+        assert_eq!(event.final_state().count(), 11);
+        assert!(event.final_state().all(|p| p.status == 1));
+
+        let [beam1, beam2] = event.beam_particles().unwrap();
+        assert_eq!(beam1.id, 2212);
+        assert_eq!(beam2.id, 2212);
+        assert_eq!(beam1.p[3], -beam2.p[3]);
+
+        assert!(Event::default().beam_particles().is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_barcode_lookup() {
+        let event = sample_event().await;
+        assert_eq!(event.vertex_by_barcode(-13).unwrap().barcode, -13);
+        assert!(event.vertex_by_barcode(1).is_none());
+
+        let index = event.build_barcode_index();
+        assert!(index.vertex(0).is_none());
+        for vertex in &event.vertices {
+            assert_eq!(index.vertex(vertex.barcode), Some(vertex));
+        }
+
+        // walk from the first beam particle to a final-state particle
+        let [beam, _] = event.beam_particles().unwrap();
+        let mut particle = beam;
+        let mut nsteps = 0;
+        while particle.end_vtx != 0 {
+            let vertex = index.vertex(particle.end_vtx).unwrap();
+            assert_eq!(
+                vertex,
+                event.vertex_by_barcode(particle.end_vtx).unwrap()
+            );
+            particle = &vertex.particles_out[0];
+            nsteps += 1;
+        }
+        assert_eq!(particle.status, 1);
+        assert_eq!(particle.id, -13);
+        assert_eq!(nsteps, 5);
+
+        assert_eq!(event.particle_by_barcode(34), Some(particle));
+        assert!(event.particle_by_barcode(0).is_none());
+        assert!(index.particle(0).is_none());
+        for particle in event.particles() {
+            assert_eq!(index.particle(particle.barcode), Some(particle));
+        }
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_particle_barcode_round_trip() {
+        #[cfg(feature = "sync")]
+        use std::io::BufReader;
+        #[cfg(feature = "tokio")]
+        use tokio::io::BufReader;
+
+        let event = sample_event().await;
+        let barcodes: Vec<_> = event.particles().map(|p| p.barcode).collect();
+        assert_eq!(barcodes[..3], [3, 4, 9]);
+
+        let mut buf = Vec::<u8>::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let mut reader = reader::Reader::from(BufReader::new(buf.as_slice()));
+        let event = reader.next().await.unwrap().unwrap();
+        let new_barcodes: Vec<_> =
+            event.particles().map(|p| p.barcode).collect();
+        assert_eq!(barcodes, new_barcodes);
+        assert_eq!(event.particle_by_barcode(35).unwrap().id, 14);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_vertex_particle_numbers() {
+        let event = sample_event().await;
+        let mut buf = Vec::<u8>::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        // compare barcode, status, number of orphans, number of
+        // outgoing particles, and number of weights
+        let vertex_fields = |txt: &[u8]| -> Vec<Vec<String>> {
+            std::str::from_utf8(txt)
+                .unwrap()
+                .lines()
+                .filter(|l| l.starts_with('V'))
+                .map(|l| {
+                    let fields: Vec<_> = l.split_whitespace().collect();
+                    [1, 2, 7, 8, 9]
+                        .into_iter()
+                        .map(|i| fields[i].to_owned())
+                        .collect()
+                })
+                .collect()
+        };
+        let orig = vertex_fields(EVENT_TXT);
+        assert_eq!(orig.len(), 23);
+        assert_eq!(orig, vertex_fields(&buf));
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_read_uncompressed() {
+        let mut reader =
+            reader::Reader::with_decompression(EVENT_TXT).await.unwrap();
+        let event = reader.next().await.unwrap().unwrap();
+        let expected = sample_event().await;
+        assert_eq!(event, expected);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_read_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(EVENT_TXT).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader =
+            reader::Reader::with_decompression(compressed.as_slice())
+                .await
+                .unwrap();
+        let event = reader.next().await.unwrap().unwrap();
+        let expected = sample_event().await;
+        assert_eq!(event, expected);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_read_gzip_members() {
+        use std::io::Write;
+
+        let mut compressed = Vec::new();
+        for _ in 0..2 {
+            let mut encoder = flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            );
+            encoder.write_all(EVENT_TXT).unwrap();
+            compressed.append(&mut encoder.finish().unwrap());
+        }
+
+        let mut reader =
+            reader::Reader::with_decompression(compressed.as_slice())
+                .await
+                .unwrap();
+        let expected = sample_event().await;
+        for _ in 0..2 {
+            let event = reader.next().await.unwrap().unwrap();
+            assert_eq!(event, expected);
+        }
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_open() {
+        let buf = sample_events(2).await;
+        let name = format!("hepmc2-tst-open-{}.hepmc2", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, &buf).unwrap();
+        let reader = reader::Reader::open(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        let count = reader.unwrap().count_events().await.unwrap();
+        assert_eq!(count, 2);
+
+        let missing = reader::Reader::open(&path).await;
+        assert!(missing.is_err());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_open_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(EVENT_TXT).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let name = format!("hepmc2-tst-open-{}.hepmc2.gz", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, compressed).unwrap();
+        let reader = reader::Reader::open(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        let mut reader = reader.unwrap();
+        let event = reader.next().await.unwrap().unwrap();
+        let expected = sample_event().await;
+        assert_eq!(event, expected);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_create_gzip() {
+        let name =
+            format!("hepmc2-tst-create-{}.hepmc2.gz", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let event = sample_event().await;
+        let mut writer = writer::Writer::create(&path).await.unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut decoder = flate2::read::GzDecoder::new(compressed.as_slice());
+        let mut txt = String::new();
+        std::io::Read::read_to_string(&mut decoder, &mut txt).unwrap();
+        assert!(txt.ends_with("HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+
+        let mut reader =
+            reader::Reader::with_decompression(compressed.as_slice())
+                .await
+                .unwrap();
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read, event);
+        let next = reader.next().await;
+        assert!(next.is_none());
+    }
+
+    #[maybe_async::maybe_async]
+    async fn compression_round_trip(compression: compression::Compression) {
+        #[cfg(feature = "sync")]
+        use std::io::Read;
+        #[cfg(feature = "tokio")]
+        use tokio::io::AsyncReadExt;
+
+        let event = sample_event().await;
+        let mut buf = Vec::<u8>::new();
+        let mut writer =
+            writer::Writer::with_compression(&mut buf, compression)
+                .await
+                .unwrap();
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let mut decompressor = compression::Decompressor::new(buf.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(decompressor.compression(), compression);
+        let mut txt = String::new();
+        decompressor.read_to_string(&mut txt).await.unwrap();
+        assert!(txt.ends_with("HepMC::IO_GenEvent-END_EVENT_LISTING\n"));
+
+        let mut reader = reader::Reader::with_decompression(buf.as_slice())
+            .await
+            .unwrap();
+        let read = reader.next().await.unwrap().unwrap();
+        assert_eq!(read, event);
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_round_trip_uncompressed() {
+        compression_round_trip(compression::Compression::None).await
+    }
+
+    #[cfg(feature = "flate2")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_round_trip_gzip() {
+        compression_round_trip(compression::Compression::Gzip).await
+    }
+
+    #[cfg(feature = "zstd")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_round_trip_zstd() {
+        compression_round_trip(compression::Compression::Zstd).await
+    }
+
+    #[cfg(feature = "xz")]
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_round_trip_xz() {
+        compression_round_trip(compression::Compression::Xz).await
+    }
+
+    #[maybe_async::test(
+        feature = "sync",
+        async(
+            all(not(feature = "sync"), feature = "tokio"),
+            tokio::test(flavor = "multi_thread")
+        )
+    )]
+    async fn tst_write_precision() {
+        let event = sample_event().await;
+        let mut buf = Vec::<u8>::new();
+        let mut writer = writer::Writer::try_from(&mut buf).await.unwrap();
+        assert_eq!(writer.precision(), None);
+        writer.set_precision(Some(17));
+        assert_eq!(writer.precision(), Some(17));
+        writer.write(&event).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let records = |txt: &[u8]| -> Vec<String> {
+            std::str::from_utf8(txt)
+                .unwrap()
+                .lines()
+                .filter(|l| l.starts_with(['V', 'P', 'C', 'U', 'N']))
+                .map(|l| l.to_owned())
+                .collect()
+        };
+        assert_eq!(records(EVENT_TXT), records(&buf));
+    }
+
     const EVENT_TXT: &[u8] = br#"
 HepMC::Version 2.06.09
 HepMC::IO_GenEvent-START_EVENT_LISTING
@@ -0,0 +1,47 @@
+//! Reading from memory-mapped files
+//!
+//! This module is only available with the `mmap` and `sync` features.
+//! See [Reader::mmap].
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+use crate::reader::Reader;
+
+use memmap2::Mmap;
+
+/// Stream over the contents of a memory-mapped file
+pub type MmapStream = Cursor<Mmap>;
+
+impl Reader<MmapStream> {
+    /// Map the file at `path` into memory and construct a `Reader` for it
+    ///
+    /// The returned `Reader` supports all settings. Each line is
+    /// copied straight from the mapped file contents, without first
+    /// going through a read buffer like with a [BufReader](io::BufReader).
+    /// The `Reader` is also seekable, see [rewind](Reader::rewind).
+    ///
+    /// # Safety
+    ///
+    /// The file is mapped into memory, so its contents must not be
+    /// modified or truncated while the `Reader` exists. Doing so from
+    /// this or any other process is undefined behaviour.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use hepmc2::Reader;
+    ///
+    /// // SAFETY: the file is not modified while it is being read
+    /// let reader = unsafe { Reader::mmap("events.hepmc2")? };
+    /// for event in reader {
+    ///     println!("Read event {}", event?.number);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub unsafe fn mmap<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        Ok(Self::from(Cursor::new(mmap)))
+    }
+}
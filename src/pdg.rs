@@ -0,0 +1,116 @@
+//! Names and charges of common particles
+//!
+//! This module is only available with the `pdg` feature. It covers
+//! the Standard Model particles and the most common light and heavy
+//! flavour hadrons, using the naming conventions of Pythia 8.
+use crate::event::Particle;
+
+/// Name, name of the antiparticle, and charge in units of e/3
+///
+/// The antiparticle name is `None` for self-conjugate particles.
+fn lookup(id: i32) -> Option<(&'static str, Option<&'static str>, i32)> {
+    let entry = match id.unsigned_abs() {
+        1 => ("d", Some("dbar"), -1),
+        2 => ("u", Some("ubar"), 2),
+        3 => ("s", Some("sbar"), -1),
+        4 => ("c", Some("cbar"), 2),
+        5 => ("b", Some("bbar"), -1),
+        6 => ("t", Some("tbar"), 2),
+        11 => ("e-", Some("e+"), -3),
+        12 => ("nu_e", Some("nu_ebar"), 0),
+        13 => ("mu-", Some("mu+"), -3),
+        14 => ("nu_mu", Some("nu_mubar"), 0),
+        15 => ("tau-", Some("tau+"), -3),
+        16 => ("nu_tau", Some("nu_taubar"), 0),
+        21 => ("g", None, 0),
+        22 => ("gamma", None, 0),
+        23 => ("Z0", None, 0),
+        24 => ("W+", Some("W-"), 3),
+        25 => ("h0", None, 0),
+        111 => ("pi0", None, 0),
+        113 => ("rho0", None, 0),
+        130 => ("K_L0", None, 0),
+        211 => ("pi+", Some("pi-"), 3),
+        213 => ("rho+", Some("rho-"), 3),
+        221 => ("eta", None, 0),
+        223 => ("omega", None, 0),
+        310 => ("K_S0", None, 0),
+        311 => ("K0", Some("Kbar0"), 0),
+        321 => ("K+", Some("K-"), 3),
+        333 => ("phi", None, 0),
+        411 => ("D+", Some("D-"), 3),
+        421 => ("D0", Some("Dbar0"), 0),
+        443 => ("J/psi", None, 0),
+        511 => ("B0", Some("Bbar0"), 0),
+        521 => ("B+", Some("B-"), 3),
+        2112 => ("n0", Some("nbar0"), 0),
+        2212 => ("p+", Some("pbar-"), 3),
+        3122 => ("Lambda0", Some("Lambdabar0"), 0),
+        _ => return None,
+    };
+    Some(entry)
+}
+
+impl Particle {
+    /// The name of the particle, if it is a known particle
+    ///
+    /// Negative ids of self-conjugate particles like the photon
+    /// are invalid and return `None`.
+    pub fn pdg_name(&self) -> Option<&'static str> {
+        let (name, antiname, _) = lookup(self.id)?;
+        if self.id > 0 {
+            Some(name)
+        } else {
+            antiname
+        }
+    }
+
+    /// Whether the particle is a neutrino or antineutrino
+    pub fn is_neutrino(&self) -> bool {
+        matches!(self.id.unsigned_abs(), 12 | 14 | 16)
+    }
+
+    /// The electric charge in units of the elementary charge, if it
+    /// is a known particle
+    pub fn charge(&self) -> Option<f64> {
+        let (_, antiname, charge3) = lookup(self.id)?;
+        let charge = f64::from(charge3) / 3.;
+        if self.id > 0 {
+            Some(charge)
+        } else {
+            antiname.map(|_| -charge)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particle(id: i32) -> Particle {
+        Particle {
+            id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tst_pdg() {
+        assert_eq!(particle(13).pdg_name(), Some("mu-"));
+        assert_eq!(particle(13).charge(), Some(-1.));
+        assert_eq!(particle(-13).pdg_name(), Some("mu+"));
+        assert_eq!(particle(-13).charge(), Some(1.));
+        assert_eq!(particle(2212).pdg_name(), Some("p+"));
+        assert_eq!(particle(2212).charge(), Some(1.));
+        assert_eq!(particle(-2212).charge(), Some(-1.));
+        assert_eq!(particle(2).charge(), Some(2. / 3.));
+        assert_eq!(particle(-1).pdg_name(), Some("dbar"));
+        assert_eq!(particle(-1).charge(), Some(1. / 3.));
+        assert_eq!(particle(22).pdg_name(), Some("gamma"));
+        assert_eq!(particle(22).charge(), Some(0.));
+        assert_eq!(particle(-22).pdg_name(), None);
+        assert_eq!(particle(-22).charge(), None);
+        assert_eq!(particle(0).pdg_name(), None);
+        assert_eq!(particle(9_999_999).charge(), None);
+    }
+}
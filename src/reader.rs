@@ -1,30 +1,108 @@
-use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::default::Default;
 use std::fmt::{self, Display};
 use std::io;
 use std::num::{ParseFloatError, TryFromIntError};
+use std::str::FromStr;
 
+use crate::compression::Decompressor;
 use crate::event::*;
+use crate::ignored::Ignored;
+use crate::record::Record;
+use crate::stats::Statistics;
 
-use hepmc2_macros::read_bound;
+use hepmc2_macros::{read_bound, seek_bound};
+use log::{debug, warn};
 use nom::{
     bytes::complete::{take_until, take_while1},
-    character::complete::{char, i32, space1, u64},
+    character::complete::{char, i32, i64, space1, u64},
     combinator::opt,
-    number::complete::double,
-    sequence::{delimited, preceded, tuple},
-    IResult,
+    sequence::{delimited, preceded},
+    IResult, InputLength,
 };
 use thiserror::Error;
 
 const BUF_SIZE: usize = 256;
 
+#[cfg(feature = "sync")]
+type FileStream = std::io::BufReader<std::fs::File>;
+#[cfg(feature = "tokio")]
+type FileStream = tokio::io::BufReader<tokio::fs::File>;
+
 /// Reader for the HepMC2 format
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Reader<T> {
     stream: T,
     line: String,
     line_nr: usize,
+    offset: u64,
+    event_offset: Option<u64>,
+    event_line_nr: usize,
+    version: Option<String>,
+    banner: Option<String>,
+    error_policy: ErrorPolicy,
+    unknown_line_policy: UnknownLinePolicy,
+    trailing_token_policy: TrailingTokenPolicy,
+    /// Errors in skipped events
+    errors: Ignored<Vec<LineParseError>>,
+    intern_weight_names: bool,
+    detect_duplicate_vertices: bool,
+    strict: bool,
+    weight_names: Option<WeightNames>,
+    #[cfg(feature = "tokio")]
+    resume: Ignored<ResumeState>,
+}
+
+/// How the [Reader] handles malformed events
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ErrorPolicy {
+    /// Return the error and stop at the malformed event
+    #[default]
+    FailFast,
+    /// Record the error, skip to the next event, and continue
+    ///
+    /// I/O errors are always returned.
+    SkipEvent,
+}
+
+/// How the [Reader] handles lines with an unrecognised prefix
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum UnknownLinePolicy {
+    /// Treat the line as a parse error
+    #[default]
+    Error,
+    /// Ignore the line
+    Skip,
+    /// Store the line in [Event::raw_extra]
+    Keep,
+}
+
+/// How the [Reader] handles extra tokens at the end of record lines
+///
+/// This applies to event (`E`) and particle (`P`) lines, which some
+/// generators extend with additional fields.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum TrailingTokenPolicy {
+    /// Ignore the extra tokens
+    #[default]
+    Ignore,
+    /// Treat extra tokens as a parse error
+    Error,
+}
+
+/// State of an interrupted read
+///
+/// Futures returned by the async API may be dropped at any await
+/// point, for example when [Reader] is polled as a
+/// [Stream](futures_core::Stream). The partially read line and event
+/// are kept here, so that the next read continues where the previous
+/// one stopped.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default)]
+struct ResumeState {
+    partial_line: Vec<u8>,
+    event: Option<(Event, Filled)>,
+    skipping: bool,
 }
 
 impl<T> Reader<T> {
@@ -32,6 +110,169 @@ impl<T> Reader<T> {
     pub fn into_inner(self) -> T {
         self.stream
     }
+
+    /// Set how malformed events are handled
+    ///
+    /// By default, the reader stops at the first malformed event.
+    /// With [ErrorPolicy::SkipEvent], the error is logged and
+    /// recorded in [errors](Self::errors), and reading continues
+    /// with the next event.
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+
+    /// How malformed events are handled
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+
+    /// Set how lines with an unrecognised prefix are handled
+    ///
+    /// By default, such lines are a parse error.
+    pub fn set_unknown_line_policy(&mut self, policy: UnknownLinePolicy) {
+        self.unknown_line_policy = policy;
+    }
+
+    /// How lines with an unrecognised prefix are handled
+    pub fn unknown_line_policy(&self) -> UnknownLinePolicy {
+        self.unknown_line_policy
+    }
+
+    /// Set how extra tokens at the end of event and particle lines
+    /// are handled
+    ///
+    /// By default, such tokens are ignored.
+    pub fn set_trailing_token_policy(&mut self, policy: TrailingTokenPolicy) {
+        self.trailing_token_policy = policy;
+    }
+
+    /// How extra tokens at the end of event and particle lines are
+    /// handled
+    pub fn trailing_token_policy(&self) -> TrailingTokenPolicy {
+        self.trailing_token_policy
+    }
+
+    /// Set whether weight names are shared between events
+    ///
+    /// By default, each event gets its own copy of the weight names
+    /// in [Event::weight_names]. If interning is enabled, events with
+    /// the same names as the previous one share a single allocation,
+    /// see [WeightNames].
+    pub fn set_intern_weight_names(&mut self, intern: bool) {
+        self.intern_weight_names = intern;
+    }
+
+    /// Whether weight names are shared between events
+    pub fn intern_weight_names(&self) -> bool {
+        self.intern_weight_names
+    }
+
+    /// Set whether repeated vertex barcodes are detected
+    ///
+    /// If enabled, a vertex with the same barcode as a previous
+    /// vertex in the same event results in a
+    /// [ParseError::DuplicateVertex]. This is disabled by default,
+    /// since it makes reading slower.
+    pub fn set_detect_duplicate_vertices(&mut self, detect: bool) {
+        self.detect_duplicate_vertices = detect;
+    }
+
+    /// Whether repeated vertex barcodes are detected
+    pub fn detect_duplicate_vertices(&self) -> bool {
+        self.detect_duplicate_vertices
+    }
+
+    /// Set whether to check the number of fields in each line
+    ///
+    /// If enabled, each line has to contain exactly the number of
+    /// fields required by HepMC 2.06.09, otherwise reading fails with
+    /// a [ParseError::FieldCount]. In particular, PDF information
+    /// lines have to include the PDF ids. This is disabled by
+    /// default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Whether the number of fields in each line is checked
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// The number of the last line read, counting from one
+    ///
+    /// The reader reads one line ahead, so after reading an event
+    /// this is the line number of the following event line. This is
+    /// zero if nothing has been read yet.
+    pub fn line_number(&self) -> usize {
+        self.line_nr
+    }
+
+    /// Errors in events skipped with [ErrorPolicy::SkipEvent]
+    pub fn errors(&self) -> &[LineParseError] {
+        &self.errors
+    }
+
+    /// The HepMC version declared in the header, e.g. `2.06.09`
+    ///
+    /// This is `None` until the header has been read, either
+    /// explicitly with `read_header()` or implicitly when reading or
+    /// skipping the first event. It stays `None` if there is no
+    /// version line. For concatenated files, this is the version of
+    /// the most recently read header.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// The `HepMC::IO_GenEvent` banner line from the header
+    ///
+    /// See [version](Self::version) for when this is available.
+    pub fn banner(&self) -> Option<&str> {
+        self.banner.as_deref()
+    }
+
+    /// Byte offset of the event most recently returned by `next()`
+    /// or skipped with `skip_event()`
+    ///
+    /// For a seekable stream, reading can be resumed from this event
+    /// with [seek_to_offset](Self::seek_to_offset).
+    pub fn event_offset(&self) -> Option<u64> {
+        self.event_offset
+    }
+
+    /// The number of bytes read from the stream so far
+    ///
+    /// This includes header lines and is useful for progress
+    /// reporting. After [rewind](Self::rewind) or
+    /// [seek_to_offset](Self::seek_to_offset) it is the current
+    /// position in the stream.
+    pub fn bytes_read(&self) -> u64 {
+        self.offset
+    }
+
+    /// Byte offset of the start of the current line
+    fn line_offset(&self) -> u64 {
+        self.offset - self.line.len() as u64
+    }
+
+    /// Whether a partially read event is continued by the next read
+    fn resuming_event(&self) -> bool {
+        #[cfg(feature = "sync")]
+        return false;
+        #[cfg(feature = "tokio")]
+        return self.resume.event.is_some();
+    }
+
+    fn reset(&mut self, offset: u64) {
+        self.line.clear();
+        self.line_nr = 0;
+        self.offset = offset;
+        self.event_offset = None;
+        self.event_line_nr = 0;
+        #[cfg(feature = "tokio")]
+        {
+            self.resume = Default::default();
+        }
+    }
 }
 
 #[read_bound]
@@ -40,97 +281,819 @@ impl<T> Reader<T> {
     pub fn new(stream: T) -> Self {
         stream.into()
     }
-}
 
-#[read_bound]
-impl<T> From<T> for Reader<T> {
-    fn from(stream: T) -> Self {
+    /// Construct a new Reader with the given initial line buffer capacity
+    ///
+    /// The default capacity is 256 bytes. A larger capacity avoids
+    /// reallocations for files with very long lines.
+    pub fn with_capacity(stream: T, capacity: usize) -> Self {
         Self {
             stream,
-            line: String::with_capacity(BUF_SIZE),
+            line: String::with_capacity(capacity),
             line_nr: 0,
+            offset: 0,
+            event_offset: None,
+            event_line_nr: 0,
+            version: None,
+            banner: None,
+            error_policy: Default::default(),
+            unknown_line_policy: Default::default(),
+            trailing_token_policy: Default::default(),
+            errors: Default::default(),
+            intern_weight_names: false,
+            detect_duplicate_vertices: false,
+            strict: false,
+            weight_names: None,
+            #[cfg(feature = "tokio")]
+            resume: Default::default(),
         }
     }
 }
 
+#[read_bound]
+impl<T> Reader<Decompressor<T>> {
+    /// Construct a new Reader, decompressing the input if necessary
+    ///
+    /// See the [compression](crate::compression) module for the
+    /// supported formats.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```no_run")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::Reader;
+    ///
+    /// use std::io::BufReader;
+    /// use std::fs::File;
+    ///
+    /// let input = BufReader::new(File::open("events.hepmc2.gz")?);
+    /// let reader = Reader::with_decompression(input)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn with_decompression(stream: T) -> Result<Self, io::Error> {
+        Ok(Decompressor::new(stream).await?.into())
+    }
+}
+
+impl Reader<Decompressor<FileStream>> {
+    /// Open the file at `path` for reading
+    ///
+    /// The input is decompressed if necessary, see
+    /// [with_decompression](Self::with_decompression).
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```no_run")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::Reader;
+    ///
+    /// let reader = Reader::open("events.hepmc2.gz")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn open<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<Self, io::Error> {
+        #[cfg(feature = "sync")]
+        let file = std::fs::File::open(path)?;
+        #[cfg(feature = "tokio")]
+        let file = tokio::fs::File::open(path).await?;
+        Self::with_decompression(FileStream::new(file)).await
+    }
+}
+
+#[read_bound]
+impl<T> From<T> for Reader<T> {
+    fn from(stream: T) -> Self {
+        Self::with_capacity(stream, BUF_SIZE)
+    }
+}
+
 #[read_bound]
 impl<T> Reader<T> {
+    /// Replace the current line by the next one
+    ///
+    /// Returns `false` at the end of the stream.
+    #[maybe_async::sync_impl]
+    fn read_next_line(&mut self) -> Result<bool, io::Error> {
+        self.line.clear();
+        let nbytes = self.stream.read_line(&mut self.line)?;
+        if nbytes == 0 {
+            return Ok(false);
+        }
+        self.line_nr += 1;
+        self.offset += nbytes as u64;
+        Ok(true)
+    }
+
+    /// Replace the current line by the next one
+    ///
+    /// Returns `false` at the end of the stream. Unlike `read_line`,
+    /// this is cancellation safe: if the future is dropped, the
+    /// current line is unchanged and the bytes read so far are kept
+    /// for the next call.
+    #[maybe_async::async_impl]
+    async fn read_next_line(&mut self) -> Result<bool, io::Error> {
+        let partial_line = &mut self.resume.partial_line;
+        self.stream.read_until(b'\n', partial_line).await?;
+        self.line.clear();
+        if partial_line.is_empty() {
+            return Ok(false);
+        }
+        let nbytes = partial_line.len();
+        let res = std::str::from_utf8(partial_line)
+            .map(|line| self.line.push_str(line))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+        partial_line.clear();
+        res?;
+        self.line_nr += 1;
+        self.offset += nbytes as u64;
+        Ok(true)
+    }
+
+    /// Skip header and footer lines up to the next event
+    ///
+    /// In concatenated files, the footer of one listing is followed
+    /// by the header of the next one. Both are skipped, and the
+    /// version and banner are updated from the new header.
     #[maybe_async::maybe_async]
     async fn skip_headers(&mut self) -> Result<(), io::Error> {
         while self.line.trim().is_empty() || self.line.starts_with("HepMC") {
-            self.line.clear();
-            if self.stream.read_line(&mut self.line).await? == 0 {
+            if let Some(version) = self.line.strip_prefix("HepMC::Version") {
+                self.version = Some(version.trim().to_owned());
+            } else if self.line.starts_with("HepMC::IO_GenEvent-START") {
+                self.banner = Some(self.line.trim().to_owned());
+            } else if self.line.starts_with("HepMC::IO_GenEvent-END") {
+                debug!("End of event listing at line {}", self.line_nr);
+            }
+            if !self.read_next_line().await? {
                 break;
             }
-            self.line_nr += 1;
         }
         Ok(())
     }
 
+    /// Read the header preceding the first event
+    ///
+    /// This is done automatically when reading the first event, but
+    /// can be called explicitly to access the [version](Self::version)
+    /// beforehand.
     #[maybe_async::maybe_async]
-    async fn parse_event_inner(&mut self) -> Result<Event, ParseError> {
-        let mut event = parse_event_line(&self.line)?;
+    pub async fn read_header(&mut self) -> Result<(), io::Error> {
+        self.skip_headers().await
+    }
+
+    #[maybe_async::maybe_async]
+    async fn parse_event_inner(
+        &mut self,
+        event: &mut Event,
+    ) -> Result<(), ParseError> {
+        #[cfg(feature = "tokio")]
+        let resumed = self.resume.event.take();
+        #[cfg(feature = "sync")]
+        let resumed = None;
+        let mut filled = match resumed {
+            Some((partial, filled)) => {
+                *event = partial;
+                filled
+            }
+            None => {
+                self.event_offset = Some(self.line_offset());
+                self.event_line_nr = self.line_nr;
+                let line = strip_line_ending(&self.line);
+                if self.strict {
+                    check_field_count(line)
+                        .map_err(|err| truncation_error(&self.line, err))?;
+                }
+                let mut filled = Filled::default();
+                parse_event_line_into(line, event, &mut filled)
+                    .and_then(|rest| {
+                        check_trailing(rest, self.trailing_token_policy)
+                    })
+                    .map_err(|err| truncation_error(&self.line, err))?;
+                filled
+            }
+        };
+        let mut vertex_barcodes = BTreeSet::new();
+        if self.detect_duplicate_vertices {
+            let vertices = &event.vertices[..filled.vertices];
+            vertex_barcodes.extend(vertices.iter().map(|v| v.barcode));
+        }
         loop {
-            self.line.clear();
-            if self.stream.read_line(&mut self.line).await? == 0 {
-                break;
+            #[cfg(feature = "sync")]
+            let read = self.read_next_line();
+            #[cfg(feature = "tokio")]
+            let read = {
+                // keep the partial event in case this future is dropped
+                self.resume.event = Some((std::mem::take(event), filled));
+                let read = self.read_next_line().await;
+                (*event, filled) = self.resume.event.take().unwrap();
+                read
             };
-            self.line_nr += 1;
-            match self.line.as_bytes().first() {
-                Some(b'E') => break,
-                Some(b'V') => parse_vertex_line(&self.line, &mut event)?,
-                Some(b'P') => parse_particle_line(&self.line, &mut event)?,
-                Some(b'U') => parse_units_line(&self.line, &mut event)?,
-                Some(b'F') => parse_pdf_info_line(&self.line, &mut event)?,
-                Some(b'H') => {
-                    if self.line.starts_with("HepMC") {
-                        continue;
+            if !read? {
+                if filled.is_incomplete() {
+                    return Err(ParseError::UnexpectedEof);
+                }
+                break;
+            }
+            if self.at_event_end() {
+                break;
+            }
+            let line = strip_line_ending(&self.line);
+            if self.strict {
+                check_field_count(line)
+                    .map_err(|err| truncation_error(&self.line, err))?;
+            }
+            if self.intern_weight_names && line.starts_with('N') {
+                parse_shared_weight_names_line(
+                    line,
+                    event,
+                    &mut self.weight_names,
+                )
+            } else {
+                parse_record_line(
+                    line,
+                    event,
+                    &mut filled,
+                    self.unknown_line_policy,
+                    self.trailing_token_policy,
+                )
+            }
+            .map_err(|err| truncation_error(&self.line, err))?;
+            if self.detect_duplicate_vertices && line.starts_with('V') {
+                let barcode = filled.current_vertex(event).unwrap().barcode;
+                if !vertex_barcodes.insert(barcode) {
+                    return Err(ParseError::DuplicateVertex(barcode));
+                }
+            }
+        }
+        filled.finish(event);
+        Ok(())
+    }
+
+    #[maybe_async::maybe_async]
+    async fn parse_event(
+        &mut self,
+        event: &mut Event,
+    ) -> Result<(), LineParseError> {
+        self.parse_event_inner(event).await.map_err(|err| {
+            LineParseError::new(err, self.line.clone(), self.line_nr)
+        })
+    }
+
+    #[maybe_async::maybe_async]
+    async fn skip_event_inner(&mut self) -> Result<bool, ParseError> {
+        self.skip_headers().await?;
+        if self.line.is_empty() {
+            return Ok(false);
+        }
+        if !self.line.starts_with('E') {
+            return Err(ParseError::bad_prefix(&self.line));
+        }
+        self.event_offset = Some(self.line_offset());
+        self.event_line_nr = self.line_nr;
+        self.skip_to_next_event_line().await?;
+        Ok(true)
+    }
+
+    #[maybe_async::maybe_async]
+    async fn skip_to_next_event_line(&mut self) -> Result<(), io::Error> {
+        loop {
+            if !self.read_next_line().await? {
+                break;
+            }
+            if self.at_event_end() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the current line is past the end of the current event
+    ///
+    /// This is the case for the next event line and for listing
+    /// footers and headers.
+    fn at_event_end(&self) -> bool {
+        self.line.starts_with('E') || self.line.starts_with("HepMC")
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_event(&mut self) -> Option<Result<Event, LineParseError>> {
+        let mut event = Event::default();
+        match self.next_event_into(&mut event).await? {
+            Ok(()) => Some(Ok(event)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn skip_malformed_event(&mut self) -> Result<(), io::Error> {
+        #[cfg(feature = "tokio")]
+        {
+            self.resume.skipping = true;
+        }
+        let res = self.skip_to_next_event_line().await;
+        #[cfg(feature = "tokio")]
+        {
+            self.resume.skipping = false;
+        }
+        res
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_event_into(
+        &mut self,
+        event: &mut Event,
+    ) -> Option<Result<(), LineParseError>> {
+        #[cfg(feature = "tokio")]
+        if self.resume.skipping {
+            if let Err(err) = self.skip_malformed_event().await {
+                return Some(Err(LineParseError::new(
+                    err.into(),
+                    self.line.clone(),
+                    self.line_nr,
+                )));
+            }
+        }
+        loop {
+            if !self.resuming_event() {
+                if let Err(err) = self.skip_headers().await {
+                    return Some(Err(LineParseError::new(
+                        err.into(),
+                        self.line.clone(),
+                        self.line_nr,
+                    )));
+                }
+                if self.line.is_empty() {
+                    return None;
+                }
+            }
+            match self.parse_event(event).await {
+                Err(err)
+                    if self.error_policy == ErrorPolicy::SkipEvent
+                        && !matches!(err.err, ParseError::Io(_)) =>
+                {
+                    warn!("Skipping malformed event: {err}");
+                    self.errors.push(err);
+                    if let Err(err) = self.skip_malformed_event().await {
+                        return Some(Err(LineParseError::new(
+                            err.into(),
+                            self.line.clone(),
+                            self.line_nr,
+                        )));
                     }
-                    parse_heavy_ion_line(&self.line, &mut event)?
                 }
-                Some(b'N') => parse_weight_names_line(&self.line, &mut event)?,
-                Some(b'C') => parse_xs_info_line(&self.line, &mut event)?,
-                _ => {
-                    if self.line.trim().is_empty() {
+                res => return Some(res),
+            }
+        }
+    }
+
+    /// Read the next event into an existing `Event`
+    ///
+    /// This reuses the allocations in `event`, which is faster than
+    /// reading a new event with `next()` when the same `Event` is
+    /// filled repeatedly. Returns `false` if there was no event left,
+    /// in which case `event` is unchanged. If an error occurs, the
+    /// contents of `event` are unspecified.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::{Event, Reader};
+    ///
+    /// let input = b"E 0 0 0 0 0 0 0 0 0 0 0 0\n";
+    /// let mut reader = Reader::from(input.as_slice());
+    /// let mut event = Event::default();
+    /// while reader.read_into(&mut event)? {
+    ///     println!("Read event {}", event.number);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn read_into(
+        &mut self,
+        event: &mut Event,
+    ) -> Result<bool, LineParseError> {
+        match self.next_event_into(event).await {
+            Some(res) => res.map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Read all remaining events and collect their statistics
+    ///
+    /// See [Statistics] for details.
+    #[maybe_async::maybe_async]
+    pub async fn statistics(mut self) -> Result<Statistics, LineParseError> {
+        let mut stats = Statistics::new();
+        let mut event = Event::default();
+        while self.read_into(&mut event).await? {
+            stats.observe(&event);
+        }
+        Ok(stats)
+    }
+
+    /// Skip the next event in the stream
+    ///
+    /// This is much faster than reading the event with `next()`,
+    /// since the skipped lines are not parsed. Returns `false` if
+    /// there was no event left to skip.
+    #[maybe_async::maybe_async]
+    pub async fn skip_event(&mut self) -> Result<bool, LineParseError> {
+        self.skip_event_inner().await.map_err(|err| {
+            LineParseError::new(err, self.line.clone(), self.line_nr)
+        })
+    }
+
+    /// Skip the next `n` events in the stream
+    ///
+    /// Returns the number of events that were actually skipped,
+    /// which is less than `n` if the end of the stream was reached.
+    #[maybe_async::maybe_async]
+    pub async fn skip_events(
+        &mut self,
+        n: usize,
+    ) -> Result<usize, LineParseError> {
+        for skipped in 0..n {
+            if !self.skip_event().await? {
+                return Ok(skipped);
+            }
+        }
+        Ok(n)
+    }
+
+    /// Only yield events for which `pred` returns `true`
+    ///
+    /// Errors are always passed on.
+    pub fn filter_events<F>(self, pred: F) -> FilteredReader<T, F>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        FilteredReader {
+            reader: self,
+            pred,
+            on_event_line: false,
+        }
+    }
+
+    /// Only yield events for which `pred` returns `true`, deciding
+    /// based on the event line alone
+    ///
+    /// In contrast to [filter_events](Self::filter_events), `pred`
+    /// only sees the information from the event line starting with
+    /// `E`. In particular, the event has no vertices or particles
+    /// and no cross section, units, PDF, or heavy ion
+    /// information. Rejected events are skipped without parsing the
+    /// remaining lines.
+    pub fn filter_event_line<F>(self, pred: F) -> FilteredReader<T, F>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        FilteredReader {
+            reader: self,
+            pred,
+            on_event_line: true,
+        }
+    }
+
+    /// Yield each event together with its position in the stream
+    ///
+    /// See [EventMeta].
+    pub fn with_meta(self) -> MetaReader<T> {
+        MetaReader { reader: self }
+    }
+
+    /// Yield events in batches of `n`
+    ///
+    /// The last batch can contain fewer than `n` events. If an error
+    /// occurs, the error is yielded instead of the batch and the
+    /// events read before the error in the same batch are discarded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn chunks(self, n: usize) -> ChunkedReader<T> {
+        assert!(n > 0, "chunk size must be positive");
+        ChunkedReader {
+            reader: self,
+            chunk_size: n,
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn next_filtered<F>(
+        &mut self,
+        pred: &mut F,
+        on_event_line: bool,
+    ) -> Option<Result<Event, LineParseError>>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        loop {
+            if on_event_line {
+                if let Err(err) = self.skip_headers().await {
+                    return Some(Err(LineParseError::new(
+                        err.into(),
+                        self.line.clone(),
+                        self.line_nr,
+                    )));
+                }
+                if self.line.is_empty() {
+                    return None;
+                }
+                // errors are reported when parsing the full event
+                if let Ok(event) = parse_event_line(&self.line) {
+                    if !pred(&event) {
+                        if let Err(err) = self.skip_event().await {
+                            return Some(Err(err));
+                        }
                         continue;
-                    } else {
-                        return Err(ParseError::BadPrefix);
                     }
                 }
-            };
+            }
+            match self.next_event().await? {
+                Ok(event) if !on_event_line && !pred(&event) => continue,
+                res => return Some(res),
+            }
         }
-        Ok(event)
     }
 
     #[maybe_async::maybe_async]
-    async fn parse_event(&mut self) -> Result<Event, LineParseError> {
-        self.parse_event_inner()
-            .await
-            .map_err(|err| LineParseError {
-                err,
-                line: self.line.clone(),
-                line_nr: self.line_nr,
-            })
+    async fn count_events_inner(&mut self) -> Result<usize, ParseError> {
+        let mut count = 0;
+        loop {
+            if self.line.starts_with("E ") {
+                count += 1;
+            }
+            if !self.read_next_line().await? {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Count the remaining events in the stream
+    ///
+    /// This only looks at the first characters of each line and is
+    /// therefore much faster than counting the events returned by
+    /// `next()`.
+    #[maybe_async::maybe_async]
+    pub async fn count_events(mut self) -> Result<usize, LineParseError> {
+        self.count_events_inner().await.map_err(|err| {
+            LineParseError::new(err, self.line.clone(), self.line_nr)
+        })
     }
 
     #[maybe_async::async_impl]
     /// Read the next event from the stream
-    // NOTE: This function is a tempory measure! The proper way to replicate an iterator in async
-    // context is to implement `futures::stream::Stream`. This should be done in a later
-    // contribution.
+    ///
+    /// `Reader` also implements [Stream](futures_core::Stream) for use
+    /// with stream combinators.
     pub async fn next(&mut self) -> Option<std::result::Result<Event, LineParseError>> {
-        if let Err(err) = self.skip_headers().await {
-            return Some(Err(LineParseError {
-                err: err.into(),
-                line: self.line.clone(),
-                line_nr: self.line_nr,
-            }));
+        self.next_event().await
+    }
+}
+
+/// Events as an asynchronous stream
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn try_main() -> Result<(), Box<dyn std::error::Error>> {
+/// use futures::StreamExt;
+/// use hepmc2::Reader;
+///
+/// let input = tokio::io::BufReader::new(
+///     tokio::fs::File::open("events.hepmc2").await?
+/// );
+/// let numbers: Vec<_> = Reader::from(input)
+///     .filter_map(|event| async { event.ok() })
+///     .map(|event| event.number)
+///     .collect()
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+#[read_bound]
+impl<T> futures_core::Stream for Reader<T> {
+    type Item = Result<Event, LineParseError>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        // the future may be dropped while pending, which is fine since
+        // reading is resumed from the state kept in `self.resume`
+        let next = self.get_mut().next_event();
+        std::future::Future::poll(std::pin::pin!(next), cx)
+    }
+}
+
+/// Parse a single event
+///
+/// Leading `HepMC` header lines are ignored. It is an error if `s`
+/// does not contain exactly one event.
+///
+/// # Example
+///
+/// ```
+/// use hepmc2::reader::parse_event;
+///
+/// let event = parse_event(
+///     "E 1 -1 0 0 0 0 0 0 0 0 0 0\nU GEV MM\nC 1.0 0.1\n",
+/// )?;
+/// assert_eq!(event.number, 1);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_event(s: &str) -> Result<Event, LineParseError> {
+    let mut lines = s.lines().zip(1..);
+    let mut last_line_nr = 0;
+    let (line, line_nr) = loop {
+        match lines.next() {
+            Some((line, line_nr))
+                if line.trim().is_empty() || line.starts_with("HepMC") =>
+            {
+                last_line_nr = line_nr;
+            }
+            Some(next) => break next,
+            None => {
+                return Err(LineParseError::new(
+                    ParseError::NoEvent,
+                    String::new(),
+                    last_line_nr,
+                ))
+            }
         }
-        if self.line.is_empty() {
-            return None;
+    };
+    let to_line_err = |err, line: &str, line_nr| {
+        LineParseError::new(err, line.to_owned(), line_nr)
+    };
+    if !line.starts_with('E') {
+        let err = ParseError::bad_prefix(line);
+        return Err(to_line_err(err, line, line_nr));
+    }
+    let mut event = parse_event_line(line)
+        .map_err(|err| to_line_err(err, line, line_nr))?;
+    let mut filled = Filled::default();
+    for (line, line_nr) in lines {
+        if line.starts_with('E') {
+            return Err(to_line_err(ParseError::MultipleEvents, line, line_nr));
         }
-        Some(self.parse_event().await)
+        parse_record_line(
+            line,
+            &mut event,
+            &mut filled,
+            UnknownLinePolicy::Error,
+            TrailingTokenPolicy::Ignore,
+        )
+        .map_err(|err| to_line_err(err, line, line_nr))?;
     }
+    filled.finish(&mut event);
+    Ok(event)
+}
+
+impl FromStr for Event {
+    type Err = LineParseError;
+
+    /// Parse a single event, see [parse_event]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_event(s)
+    }
+}
+
+/// Parse a line following the event line
+pub(crate) fn parse_record_line(
+    line: &str,
+    event: &mut Event,
+    filled: &mut Filled,
+    unknown_line_policy: UnknownLinePolicy,
+    trailing_token_policy: TrailingTokenPolicy,
+) -> Result<(), ParseError> {
+    match line.as_bytes().first() {
+        Some(b'V') => parse_vertex_line(line, event, filled),
+        Some(b'P') => {
+            let rest = parse_particle_line(line, event, filled)?;
+            check_trailing(rest, trailing_token_policy)
+        }
+        Some(b'U') => parse_units_line(line, event),
+        Some(b'F') => parse_pdf_info_line(line, event),
+        Some(b'H') => {
+            if line.starts_with("HepMC") {
+                return Ok(());
+            }
+            parse_heavy_ion_line(line, event)
+        }
+        Some(b'N') => parse_weight_names_line(line, event),
+        Some(b'C') => parse_xs_info_line(line, event),
+        _ => {
+            if line.trim().is_empty() {
+                return Ok(());
+            }
+            match unknown_line_policy {
+                UnknownLinePolicy::Error => Err(ParseError::bad_prefix(line)),
+                UnknownLinePolicy::Skip => Ok(()),
+                UnknownLinePolicy::Keep => {
+                    event.raw_extra.push(strip_line_ending(line).to_owned());
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// Parse a single line without a trailing line ending into a [Record]
+pub(crate) fn parse_record(line: &str) -> Result<Record, ParseError> {
+    let mut event = Event::default();
+    let mut filled = Filled::default();
+    let record = match line.as_bytes().first() {
+        Some(b'E') => Record::EventHeader(Box::new(parse_event_line(line)?)),
+        Some(b'V') => {
+            parse_vertex_line(line, &mut event, &mut filled)?;
+            Record::Vertex(event.vertices.swap_remove(0))
+        }
+        Some(b'P') => {
+            // particles are always parsed into the current vertex
+            event.vertices.push(Vertex::default());
+            filled.vertices = 1;
+            parse_particle_line(line, &mut event, &mut filled)?;
+            let vertex = event.vertices.swap_remove(0);
+            let mut particles =
+                vertex.particles_in.into_iter().chain(vertex.particles_out);
+            Record::Particle(particles.next().unwrap())
+        }
+        Some(b'U') => {
+            parse_units_line(line, &mut event)?;
+            Record::Units(event.energy_unit, event.length_unit)
+        }
+        Some(b'C') => {
+            parse_xs_info_line(line, &mut event)?;
+            Record::CrossSection(event.xs)
+        }
+        Some(b'F') => {
+            parse_pdf_info_line(line, &mut event)?;
+            Record::PdfInfo(event.pdf_info)
+        }
+        Some(b'H') => {
+            parse_heavy_ion_line(line, &mut event)?;
+            Record::HeavyIon(event.heavy_ion_info.unwrap_or_default())
+        }
+        Some(b'N') => {
+            parse_weight_names_line(line, &mut event)?;
+            Record::WeightNames(event.weight_names.into_vec())
+        }
+        _ => Record::Unknown(line.to_owned()),
+    };
+    Ok(record)
+}
+
+#[read_bound]
+#[seek_bound]
+impl<T> Reader<T> {
+    /// Go back to the start of the stream
+    ///
+    /// This is only available for seekable streams. The header is
+    /// read again before the next event.
+    #[maybe_async::maybe_async]
+    pub async fn rewind(&mut self) -> Result<(), io::Error> {
+        self.stream.rewind().await?;
+        self.reset(0);
+        Ok(())
+    }
+
+    /// Continue reading from the given byte offset
+    ///
+    /// The offset should be one returned by
+    /// [event_offset](Self::event_offset). Line numbers in errors
+    /// are counted from this offset.
+    #[maybe_async::maybe_async]
+    pub async fn seek_to_offset(
+        &mut self,
+        offset: u64,
+    ) -> Result<(), io::Error> {
+        self.stream.seek(io::SeekFrom::Start(offset)).await?;
+        self.reset(offset);
+        Ok(())
+    }
+}
+
+/// Replace the error for an incomplete last line by `UnexpectedEof`
+///
+/// Only the last line in a stream can lack a line ending.
+pub(crate) fn truncation_error(line: &str, err: ParseError) -> ParseError {
+    if line.ends_with('\n') {
+        err
+    } else {
+        ParseError::UnexpectedEof
+    }
+}
+
+/// Remove a trailing `\n` or `\r\n`
+pub(crate) fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
 }
 
 fn whitespace(line: &str) -> IResult<&str, &str> {
@@ -149,19 +1112,55 @@ fn ws_i32(line: &str) -> IResult<&str, i32> {
     preceded(whitespace, i32)(line)
 }
 
+fn ws_i64(line: &str) -> IResult<&str, i64> {
+    preceded(whitespace, i64)(line)
+}
+
 fn ws_u64(line: &str) -> IResult<&str, u64> {
     preceded(whitespace, u64)(line)
 }
 
+#[cfg(not(feature = "fast-float"))]
 fn ws_double(line: &str) -> IResult<&str, f64> {
+    use nom::number::complete::double;
+
     preceded(whitespace, double)(line)
 }
 
+#[cfg(feature = "fast-float")]
+fn ws_double(line: &str) -> IResult<&str, f64> {
+    use nom::error::{Error, ErrorKind};
+
+    let (rest, _) = whitespace(line)?;
+    match fast_float2::parse_partial(rest) {
+        Ok((value, len)) => Ok((&rest[len..], value)),
+        Err(_) => Err(nom::Err::Error(Error::new(rest, ErrorKind::Float))),
+    }
+}
+
+fn ws_string(line: &str) -> IResult<&str, &str> {
+    preceded(whitespace, string)(line)
+}
+
 fn string(line: &str) -> IResult<&str, &str> {
     delimited(char('"'), take_until("\""), char('"'))(line)
 }
 
 fn parse_event_line(line: &str) -> Result<Event, ParseError> {
+    let mut event = Event::default();
+    parse_event_line_into(line, &mut event, &mut Filled::default())?;
+    Ok(event)
+}
+
+/// Parse an event line, resetting all other data in `event`
+///
+/// The vertices are kept and later overwritten, see [Filled].
+/// Returns the unparsed rest of the line.
+pub(crate) fn parse_event_line_into<'a>(
+    line: &'a str,
+    event: &mut Event,
+    filled: &mut Filled,
+) -> Result<&'a str, ParseError> {
     let rest = &line[1..];
 
     let (rest, event_number) = ws_i32(rest)?;
@@ -172,48 +1171,110 @@ fn parse_event_line(line: &str) -> Result<Event, ParseError> {
     let (rest, signal_process_id) = ws_i32(rest)?;
     let (rest, signal_process_vertex) = ws_i32(rest)?;
     let (rest, num_vertices) = ws_u64(rest)?;
-    let num_vertices = num_vertices.try_into()?;
-    let (rest, _beam1) = ws_nonws(rest)?;
-    let (rest, _beam2) = ws_nonws(rest)?;
+    let num_vertices: usize = num_vertices.try_into()?;
+    let (rest, beam1) = ws_i32(rest)?;
+    let (rest, beam2) = ws_i32(rest)?;
     let (mut rest, nrandom_states) = ws_u64(rest)?;
 
     let nrandom_states = nrandom_states.try_into()?;
-    let mut random_states = Vec::with_capacity(nrandom_states);
+    event.random_states.clear();
+    event.random_states.reserve(nrandom_states);
     for _ in 0..nrandom_states {
-        let (rem, random_state) = ws_i32(rest)?;
+        let (rem, random_state) = ws_i64(rest)?;
         rest = rem;
-        random_states.push(random_state);
+        event.random_states.push(random_state);
     }
     let (mut rest, nweights) = ws_u64(rest)?;
     let nweights = nweights.try_into()?;
-    let mut weights = Vec::with_capacity(nweights);
+    event.weights.clear();
+    event.weights.reserve(nweights);
     for _ in 0..nweights {
         let (rem, weight) = ws_double(rest)?;
         rest = rem;
-        weights.push(weight);
-    }
-    let event = Event {
-        number: event_number,
-        mpi,
-        scale: event_scale,
-        alpha_qcd,
-        alpha_qed,
-        signal_process_id,
-        signal_process_vertex,
-        random_states,
-        weights,
-        vertices: Vec::with_capacity(num_vertices),
-        weight_names: Default::default(),
-        xs: Default::default(),
-        energy_unit: Default::default(),
-        length_unit: Default::default(),
-        pdf_info: Default::default(),
-        heavy_ion_info: None,
+        event.weights.push(weight);
+    }
+    event.number = event_number;
+    event.mpi = mpi;
+    event.scale = event_scale;
+    event.alpha_qcd = alpha_qcd;
+    event.alpha_qed = alpha_qed;
+    event.signal_process_id = signal_process_id;
+    event.signal_process_vertex = signal_process_vertex;
+    event.beam_barcodes = [beam1, beam2];
+    let nvertices = event.vertices.len();
+    event
+        .vertices
+        .reserve(num_vertices.saturating_sub(nvertices));
+    if !event.weight_names.is_empty() {
+        // don't copy shared names just to clear them
+        event.weight_names = WeightNames::new();
+    }
+    event.xs = Default::default();
+    event.energy_unit = Default::default();
+    event.length_unit = Default::default();
+    event.units_declared = false;
+    event.pdf_info = Default::default();
+    event.heavy_ion_info = None;
+    event.raw_extra.clear();
+    *filled = Filled {
+        expected_vertices: num_vertices,
+        ..Default::default()
     };
-    Ok(event)
+    Ok(rest)
+}
+
+/// Number of vertices and particles filled in while parsing an event
+///
+/// When an `Event` is reused, it still contains the vertices and
+/// particles of the previous event. These are overwritten in order,
+/// and anything left over is removed at the end.
+///
+/// The numbers announced in the event and vertex lines are kept to
+/// detect incomplete events.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct Filled {
+    vertices: usize,
+    particles_in: usize,
+    particles_out: usize,
+    expected_vertices: usize,
+    expected_particles: usize,
 }
 
-fn parse_vertex_line(line: &str, event: &mut Event) -> Result<(), ParseError> {
+impl Filled {
+    fn current_vertex<'a>(
+        &self,
+        event: &'a mut Event,
+    ) -> Option<&'a mut Vertex> {
+        let idx = self.vertices.checked_sub(1)?;
+        event.vertices.get_mut(idx)
+    }
+
+    /// Remove left-over particles from the current vertex
+    fn truncate_current_vertex(&self, event: &mut Event) {
+        if let Some(vertex) = self.current_vertex(event) {
+            vertex.particles_in.truncate(self.particles_in);
+            vertex.particles_out.truncate(self.particles_out);
+        }
+    }
+
+    /// Whether fewer vertices or particles were read than announced
+    pub(crate) fn is_incomplete(&self) -> bool {
+        self.vertices < self.expected_vertices
+            || self.particles_in + self.particles_out < self.expected_particles
+    }
+
+    /// Remove all left-over vertices and particles
+    pub(crate) fn finish(&self, event: &mut Event) {
+        self.truncate_current_vertex(event);
+        event.vertices.truncate(self.vertices);
+    }
+}
+
+fn parse_vertex_line(
+    line: &str,
+    event: &mut Event,
+    filled: &mut Filled,
+) -> Result<(), ParseError> {
     let rest = &line[1..];
     let (rest, barcode) = ws_i32(rest)?;
     let (rest, status) = ws_i32(rest)?;
@@ -221,38 +1282,53 @@ fn parse_vertex_line(line: &str, event: &mut Event) -> Result<(), ParseError> {
     let (rest, y) = ws_double(rest)?;
     let (rest, z) = ws_double(rest)?;
     let (rest, t) = ws_double(rest)?;
-    let (rest, _num_orphans_int) = ws_i32(rest)?;
+    let (rest, num_orphans) = ws_i32(rest)?;
+    let num_orphans = usize::try_from(num_orphans).unwrap_or_default();
     let (rest, num_particles_out) = ws_u64(rest)?;
-    let num_particles_out = num_particles_out.try_into()?;
+    let num_particles_out: usize = num_particles_out.try_into()?;
     let (mut rest, num_weights) = ws_u64(rest)?;
     let num_weights = num_weights.try_into()?;
-    let mut weights = Vec::with_capacity(num_weights);
+
+    filled.truncate_current_vertex(event);
+    if filled.vertices == event.vertices.len() {
+        event.vertices.push(Vertex::default());
+    }
+    *filled = Filled {
+        vertices: filled.vertices + 1,
+        particles_in: 0,
+        particles_out: 0,
+        expected_particles: num_orphans + num_particles_out,
+        ..*filled
+    };
+    let vertex = filled.current_vertex(event).unwrap();
+    vertex.barcode = barcode;
+    vertex.status = status;
+    vertex.x = x;
+    vertex.y = y;
+    vertex.z = z;
+    vertex.t = t;
+    vertex.weights.clear();
+    vertex.weights.reserve(num_weights);
     for _ in 0..num_weights {
         let (rem, weight) = ws_double(rest)?;
         rest = rem;
-        weights.push(weight);
-    }
-    let vertex = Vertex {
-        barcode,
-        status,
-        x,
-        y,
-        z,
-        t,
-        weights,
-        particles_in: Vec::new(),
-        particles_out: Vec::with_capacity(num_particles_out),
-    };
-    event.vertices.push(vertex);
+        vertex.weights.push(weight);
+    }
+    let nparticles_out = vertex.particles_out.len();
+    vertex
+        .particles_out
+        .reserve(num_particles_out.saturating_sub(nparticles_out));
     Ok(())
 }
 
-fn parse_particle_line(
-    line: &str,
+/// Parse a particle line, returning the unparsed rest of the line
+fn parse_particle_line<'a>(
+    line: &'a str,
     event: &mut Event,
-) -> Result<(), ParseError> {
+    filled: &mut Filled,
+) -> Result<&'a str, ParseError> {
     let rest = &line[1..];
-    let (rest, _barcode) = ws_i32(rest)?;
+    let (rest, barcode) = ws_i32(rest)?;
     let (rest, id) = ws_i32(rest)?;
     let (rest, px) = ws_double(rest)?;
     let (rest, py) = ws_double(rest)?;
@@ -264,32 +1340,104 @@ fn parse_particle_line(
     let (rest, phi) = ws_double(rest)?;
     let (rest, end_vtx_code) = ws_i32(rest)?;
     let (mut rest, flowsize) = ws_i32(rest)?;
-    let mut flows = BTreeMap::new();
+    let Some(vertex) = filled.current_vertex(event) else {
+        return Err(ParseError::NoVertex);
+    };
+    // TODO: handling of end_vtx is ReaderAsciiHepMC2.cc is obscure and undocumented
+    let (particles, nfilled) = if end_vtx_code == vertex.barcode {
+        (&mut vertex.particles_in, &mut filled.particles_in)
+    } else {
+        (&mut vertex.particles_out, &mut filled.particles_out)
+    };
+    if *nfilled == particles.len() {
+        particles.push(Particle::default());
+    }
+    let particle = &mut particles[*nfilled];
+    *nfilled += 1;
+    particle.barcode = barcode;
+    particle.id = id;
+    particle.p = FourVector::txyz(e, px, py, pz);
+    particle.m = m;
+    particle.status = status;
+    particle.theta = theta;
+    particle.phi = phi;
+    particle.end_vtx = end_vtx_code;
+    particle.flows.clear();
     for _ in 0..flowsize {
         let (rem, flowidx) = ws_i32(rest)?;
         let (rem, flowval) = ws_i32(rem)?;
         rest = rem;
-        flows.insert(flowidx, flowval);
-    }
-    let particle = Particle {
-        id,
-        p: FourVector::txyz(e, px, py, pz),
-        m,
-        status,
-        theta,
-        phi,
-        flows,
-        end_vtx: end_vtx_code,
+        particle.flows.insert(flowidx, flowval);
+    }
+    Ok(rest)
+}
+
+/// Check that a line has the number of fields required by HepMC 2.06.09
+///
+/// The record prefix is not counted as a field. Lines with an unknown
+/// prefix are not checked.
+fn check_field_count(line: &str) -> Result<(), ParseError> {
+    let Some(record) = line.chars().next() else {
+        return Ok(());
     };
-    // TODO: handling of end_vtx is ReaderAsciiHepMC2.cc is obscure and undocumented
-    if let Some(vertex) = event.vertices.last_mut() {
-        if particle.end_vtx == vertex.barcode {
-            vertex.particles_in.push(particle);
-        } else {
-            vertex.particles_out.push(particle);
+    if line.starts_with("HepMC") {
+        return Ok(());
+    }
+    let rest = &line[record.len_utf8()..];
+    let fields: Vec<_> = rest.split_whitespace().collect();
+    // number given by the field at position `idx`, zero if invalid
+    let count_at = |idx: usize| -> usize {
+        fields
+            .get(idx)
+            .and_then(|f| f.parse().ok())
+            .unwrap_or_default()
+    };
+    let mut found = fields.len();
+    let expected = match record {
+        'E' => {
+            let nrandom = count_at(10);
+            12 + nrandom + count_at(11 + nrandom)
         }
-    } else {
-        return Err(ParseError::NoVertex);
+        'V' => 9 + count_at(8),
+        'P' => 12 + 2 * count_at(11),
+        'U' | 'C' => 2,
+        'F' => 9,
+        'H' => 13,
+        'N' => {
+            // weight names are quoted and may contain whitespace
+            found = 0;
+            let mut rest = rest;
+            if let Ok((rem, _)) = ws_nonws(rest) {
+                found += 1;
+                rest = rem;
+            }
+            while let Ok((rem, _)) = ws_string(rest) {
+                found += 1;
+                rest = rem;
+            }
+            found += rest.split_whitespace().count();
+            1 + count_at(0)
+        }
+        _ => return Ok(()),
+    };
+    if found != expected {
+        return Err(ParseError::FieldCount {
+            record,
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Check for extra tokens at the end of a line
+fn check_trailing(
+    rest: &str,
+    policy: TrailingTokenPolicy,
+) -> Result<(), ParseError> {
+    let rest = rest.trim();
+    if policy == TrailingTokenPolicy::Error && !rest.is_empty() {
+        return Err(ParseError::TrailingTokens(rest.to_owned()));
     }
     Ok(())
 }
@@ -301,6 +1449,7 @@ fn parse_units_line(line: &str, event: &mut Event) -> Result<(), ParseError> {
     let (_rest, length) = ws_nonws(rest)?;
     event.energy_unit = energy.parse()?;
     event.length_unit = length.parse()?;
+    event.units_declared = true;
     Ok(())
 }
 
@@ -317,13 +1466,9 @@ fn parse_pdf_info_line(
     let (rest, scale) = ws_double(rest)?;
     let (rest, xf0) = ws_double(rest)?;
     let (rest, xf1) = ws_double(rest)?;
-    let (_rest, parsed) = tuple((
-        whitespace,
-        opt(i32), // pdf_id0
-        whitespace,
-        opt(i32), // pdf_id1
-    ))(rest)?;
-    let (_, pdf_id0, _, pdf_id1) = parsed;
+    // the pdf ids are missing in files from older HepMC versions
+    let (rest, pdf_id0) = opt(preceded(whitespace, i32))(rest)?;
+    let (_rest, pdf_id1) = opt(preceded(whitespace, i32))(rest)?;
     let pdf_info = PdfInfo {
         parton_id: [id0, id1],
         x: [x0, x1],
@@ -381,11 +1526,43 @@ fn parse_weight_names_line(
     let nnames = nnames.try_into()?;
     let mut weight_names = Vec::with_capacity(nnames);
     for _ in 0..nnames {
-        let (rem, (_, name)) = tuple((whitespace, string))(rest)?;
+        let (rem, name) = ws_string(rest)?;
         weight_names.push(name.to_owned());
         rest = rem;
     }
-    event.weight_names = weight_names;
+    event.weight_names = weight_names.into();
+    Ok(())
+}
+
+/// Parse a weight names line, reusing `cache` if the names are unchanged
+fn parse_shared_weight_names_line(
+    line: &str,
+    event: &mut Event,
+    cache: &mut Option<WeightNames>,
+) -> Result<(), ParseError> {
+    let rest = &line[1..];
+    let (names, nnames) = ws_u64(rest)?;
+    let nnames: usize = nnames.try_into()?;
+    let mut cached = cache.as_deref().filter(|c| c.len() == nnames);
+    let mut rest = names;
+    for n in 0..nnames {
+        let (rem, name) = ws_string(rest)?;
+        if cached.is_some_and(|c| c[n] != name) {
+            cached = None;
+        }
+        rest = rem;
+    }
+    if cached.is_none() {
+        let mut rest = names;
+        let mut weight_names = Vec::with_capacity(nnames);
+        for _ in 0..nnames {
+            let (rem, name) = ws_string(rest)?;
+            weight_names.push(name.to_owned());
+            rest = rem;
+        }
+        *cache = Some(weight_names.into());
+    }
+    event.weight_names = cache.clone().unwrap_or_default();
     Ok(())
 }
 
@@ -406,17 +1583,172 @@ impl<T: std::io::BufRead> Iterator for Reader<T> {
     type Item = Result<Event, LineParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Err(err) = self.skip_headers() {
-            return Some(Err(LineParseError {
-                err: err.into(),
-                line: self.line.clone(),
-                line_nr: self.line_nr,
-            }));
+        self.next_event()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match self.skip_events(n) {
+            Ok(skipped) if skipped < n => None,
+            Ok(_) => self.next(),
+            Err(err) => Some(Err(err)),
         }
-        if self.line.is_empty() {
-            return None;
+    }
+}
+
+/// Reader yielding only events matching a predicate
+///
+/// See [Reader::filter_events] and [Reader::filter_event_line].
+pub struct FilteredReader<T, F> {
+    reader: Reader<T>,
+    pred: F,
+    on_event_line: bool,
+}
+
+impl<T, F> FilteredReader<T, F> {
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> Reader<T> {
+        self.reader
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T, F> Iterator for FilteredReader<T, F>
+where
+    T: std::io::BufRead,
+    F: FnMut(&Event) -> bool,
+{
+    type Item = Result<Event, LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader
+            .next_filtered(&mut self.pred, self.on_event_line)
+    }
+}
+
+#[maybe_async::async_impl]
+impl<T, F> FilteredReader<T, F>
+where
+    T: tokio::io::AsyncBufReadExt + Unpin,
+    F: FnMut(&Event) -> bool,
+{
+    /// Read the next matching event from the stream
+    pub async fn next(&mut self) -> Option<Result<Event, LineParseError>> {
+        self.reader
+            .next_filtered(&mut self.pred, self.on_event_line)
+            .await
+    }
+}
+
+/// Position of an event in the stream
+///
+/// After [rewind](Reader::rewind) or
+/// [seek_to_offset](Reader::seek_to_offset), line numbers are counted
+/// from the new position.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct EventMeta {
+    /// The number of the event line, counting from one
+    pub line_nr: usize,
+    /// The byte offset of the event line
+    pub offset: u64,
+}
+
+/// Reader yielding events together with their position
+///
+/// See [Reader::with_meta].
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct MetaReader<T> {
+    reader: Reader<T>,
+}
+
+impl<T> MetaReader<T> {
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> Reader<T> {
+        self.reader
+    }
+}
+
+#[read_bound]
+impl<T> MetaReader<T> {
+    #[maybe_async::maybe_async]
+    async fn next_with_meta(
+        &mut self,
+    ) -> Option<Result<(EventMeta, Event), LineParseError>> {
+        let event = self.reader.next_event().await?;
+        let meta = EventMeta {
+            line_nr: self.reader.event_line_nr,
+            offset: self.reader.event_offset.unwrap_or_default(),
+        };
+        Some(event.map(|event| (meta, event)))
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next event and its position from the stream
+    pub async fn next(
+        &mut self,
+    ) -> Option<Result<(EventMeta, Event), LineParseError>> {
+        self.next_with_meta().await
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead> Iterator for MetaReader<T> {
+    type Item = Result<(EventMeta, Event), LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_meta()
+    }
+}
+
+/// Reader yielding batches of events
+///
+/// See [Reader::chunks].
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct ChunkedReader<T> {
+    reader: Reader<T>,
+    chunk_size: usize,
+}
+
+impl<T> ChunkedReader<T> {
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> Reader<T> {
+        self.reader
+    }
+}
+
+#[read_bound]
+impl<T> ChunkedReader<T> {
+    #[maybe_async::maybe_async]
+    async fn next_chunk(
+        &mut self,
+    ) -> Option<Result<Vec<Event>, LineParseError>> {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        while chunk.len() < self.chunk_size {
+            match self.reader.next_event().await {
+                Some(Ok(event)) => chunk.push(event),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
         }
-        Some(self.parse_event())
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next batch of events from the stream
+    pub async fn next(&mut self) -> Option<Result<Vec<Event>, LineParseError>> {
+        self.next_chunk().await
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead> Iterator for ChunkedReader<T> {
+    type Item = Result<Vec<Event>, LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk()
     }
 }
 
@@ -429,31 +1761,87 @@ pub struct LineParseError {
     pub line: String,
     /// The line number where the error occurred
     pub line_nr: usize,
+    /// The byte offset into the line where parsing failed
+    ///
+    /// This is only known for errors from the low-level parser, see
+    /// [ParseError::Parse].
+    pub column: Option<usize>,
+}
+
+impl LineParseError {
+    pub(crate) fn new(err: ParseError, line: String, line_nr: usize) -> Self {
+        let column = match err {
+            ParseError::Parse { remaining, .. } => {
+                strip_line_ending(&line).len().checked_sub(remaining)
+            }
+            _ => None,
+        };
+        Self {
+            err,
+            line,
+            line_nr,
+            column,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("I/O error")]
     Io(#[from] io::Error),
-    #[error("Parsing error: {0}")]
-    Parse(String),
+    /// Error from the low-level parser
+    ///
+    /// `remaining` is the length of the part of the line that could
+    /// not be parsed.
+    #[error("Parsing error: {msg}")]
+    Parse { msg: String, remaining: usize },
     #[error("Integer conversion error")]
     ConvertInt(#[from] TryFromIntError),
     #[error("Float conversion error")]
     ConvertFloat(#[from] ParseFloatError),
     #[error("Enum parsing error")]
     StrumErr(#[from] strum::ParseError),
-    #[error("Unrecognized prefix")]
-    BadPrefix,
+    #[error("Unrecognized record prefix '{found}'")]
+    BadPrefix { found: char },
     #[error("Tried to add particle without vertex")]
     NoVertex,
+    #[error("No event found")]
+    NoEvent,
+    #[error("Found more than one event")]
+    MultipleEvents,
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+    #[error("Unexpected trailing tokens '{0}'")]
+    TrailingTokens(String),
+    #[error("Duplicate vertex barcode {0}")]
+    DuplicateVertex(i32),
+    #[error("Expected {expected} fields in '{record}' line, found {found}")]
+    FieldCount {
+        record: char,
+        expected: usize,
+        found: usize,
+    },
 }
 
-impl<T: Display> From<nom::Err<T>> for ParseError {
-    fn from(err: nom::Err<T>) -> Self {
+impl ParseError {
+    pub(crate) fn bad_prefix(line: &str) -> Self {
+        Self::BadPrefix {
+            found: line.chars().next().unwrap_or_default(),
+        }
+    }
+}
+
+impl<I: Display + InputLength> From<nom::Err<nom::error::Error<I>>>
+    for ParseError
+{
+    fn from(err: nom::Err<nom::error::Error<I>>) -> Self {
         match err {
-            nom::Err::Failure(err) => ParseError::Parse(err.to_string()),
-            nom::Err::Error(err) => ParseError::Parse(err.to_string()),
+            nom::Err::Failure(err) | nom::Err::Error(err) => {
+                ParseError::Parse {
+                    remaining: err.input.input_len(),
+                    msg: err.to_string(),
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,341 @@
+//! Low-level access to the individual records of a stream
+//!
+//! A [RecordReader] yields the lines of a HepMC2 stream as parsed
+//! [Record]s, without assembling them into [Event]s. This is useful
+//! for tools like validators or reformatters that work on the level
+//! of individual lines. Header and footer lines as well as empty
+//! lines are skipped.
+//!
+//! A [Visitor] can be used to transform a stream record by record,
+//! see [RecordReader::visit].
+use crate::event::{
+    CrossSection, EnergyUnit, Event, HeavyIonInfo, LengthUnit, Particle,
+    PdfInfo, Vertex,
+};
+use crate::reader::{
+    parse_record, strip_line_ending, truncation_error, LineParseError,
+};
+use crate::writer::{RecordCounts, Writer};
+
+use hepmc2_macros::read_bound;
+
+/// A single record, i.e. one line of a HepMC2 stream
+#[derive(Debug, PartialEq, Clone)]
+pub enum Record {
+    /// Event (`E`) line
+    ///
+    /// Only the fields contained in the event line are set, in
+    /// particular there are no vertices.
+    EventHeader(Box<Event>),
+    /// Vertex (`V`) line
+    ///
+    /// The vertex does not contain any particles.
+    Vertex(Vertex),
+    /// Particle (`P`) line
+    Particle(Particle),
+    /// Units (`U`) line
+    Units(EnergyUnit, LengthUnit),
+    /// Cross section (`C`) line
+    CrossSection(CrossSection),
+    /// PDF information (`F`) line
+    PdfInfo(PdfInfo),
+    /// Heavy ion information (`H`) line
+    HeavyIon(HeavyIonInfo),
+    /// Weight names (`N`) line
+    WeightNames(Vec<String>),
+    /// Line with an unrecognised prefix, without the line ending
+    Unknown(String),
+}
+
+/// Callbacks for transforming a stream record by record
+///
+/// Each method is called with the parsed record and returns whether
+/// the record should be kept. By default, all records are kept
+/// unchanged. See [RecordReader::visit].
+///
+/// The vertex and particle counts in the `E` and `V` lines are
+/// written as they were read. When dropping vertex or particle
+/// records, the visitor has to make sure that the output stays
+/// consistent.
+pub trait Visitor {
+    /// Called for each record, dispatching to the methods below
+    fn on_record(&mut self, record: &mut Record) -> bool {
+        match record {
+            Record::EventHeader(event) => self.on_event_header(event),
+            Record::Vertex(vertex) => self.on_vertex(vertex),
+            Record::Particle(particle) => self.on_particle(particle),
+            Record::Units(energy, length) => self.on_units(energy, length),
+            Record::CrossSection(xs) => self.on_cross_section(xs),
+            Record::PdfInfo(pdf) => self.on_pdf_info(pdf),
+            Record::HeavyIon(hi) => self.on_heavy_ion(hi),
+            Record::WeightNames(names) => self.on_weight_names(names),
+            Record::Unknown(line) => self.on_unknown(line),
+        }
+    }
+
+    /// Called for each event (`E`) line
+    fn on_event_header(&mut self, _event: &mut Event) -> bool {
+        true
+    }
+
+    /// Called for each vertex (`V`) line
+    fn on_vertex(&mut self, _vertex: &mut Vertex) -> bool {
+        true
+    }
+
+    /// Called for each particle (`P`) line
+    fn on_particle(&mut self, _particle: &mut Particle) -> bool {
+        true
+    }
+
+    /// Called for each units (`U`) line
+    fn on_units(
+        &mut self,
+        _energy: &mut EnergyUnit,
+        _length: &mut LengthUnit,
+    ) -> bool {
+        true
+    }
+
+    /// Called for each cross section (`C`) line
+    fn on_cross_section(&mut self, _xs: &mut CrossSection) -> bool {
+        true
+    }
+
+    /// Called for each PDF information (`F`) line
+    fn on_pdf_info(&mut self, _pdf: &mut PdfInfo) -> bool {
+        true
+    }
+
+    /// Called for each heavy ion information (`H`) line
+    fn on_heavy_ion(&mut self, _hi: &mut HeavyIonInfo) -> bool {
+        true
+    }
+
+    /// Called for each weight names (`N`) line
+    fn on_weight_names(&mut self, _names: &mut Vec<String>) -> bool {
+        true
+    }
+
+    /// Called for each line with an unrecognised prefix
+    fn on_unknown(&mut self, _line: &mut String) -> bool {
+        true
+    }
+}
+
+/// Reader for individual records
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::record::{Record, RecordReader};
+///
+/// let input = b"E 0 -1 0 0 0 0 0 1 0 0 0 0
+/// V -1 0 0 0 0 0 0 1 0
+/// P 1 22 0 0 1 1 0 1 0 0 0 0
+/// ";
+/// let records = RecordReader::from(input.as_slice());
+/// let nparticles = records
+///     .filter(|r| matches!(r, Ok(Record::Particle(_))))
+///     .count();
+/// assert_eq!(nparticles, 1);
+/// ```
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct RecordReader<T> {
+    stream: T,
+    line: String,
+    line_nr: usize,
+}
+
+impl<T> RecordReader<T> {
+    /// Construct a new RecordReader
+    pub fn new(stream: T) -> Self {
+        stream.into()
+    }
+
+    /// The number of the line containing the most recent record
+    ///
+    /// Line numbers start at 1. Before the first record has been
+    /// read, this is 0.
+    pub fn line_number(&self) -> usize {
+        self.line_nr
+    }
+
+    /// Retrieve the underlying stream
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+impl<T> From<T> for RecordReader<T> {
+    fn from(stream: T) -> Self {
+        Self {
+            stream,
+            line: String::new(),
+            line_nr: 0,
+        }
+    }
+}
+
+#[read_bound]
+impl<T> RecordReader<T> {
+    #[maybe_async::maybe_async]
+    async fn next_record(&mut self) -> Option<Result<Record, LineParseError>> {
+        loop {
+            self.line.clear();
+            match self.stream.read_line(&mut self.line).await {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => {
+                    return Some(Err(LineParseError::new(
+                        err.into(),
+                        String::new(),
+                        self.line_nr,
+                    )))
+                }
+            }
+            self.line_nr += 1;
+            let line = strip_line_ending(&self.line);
+            if line.trim().is_empty() || line.starts_with("HepMC") {
+                continue;
+            }
+            let record = parse_record(line).map_err(|err| {
+                LineParseError::new(
+                    truncation_error(&self.line, err),
+                    self.line.clone(),
+                    self.line_nr,
+                )
+            });
+            return Some(record);
+        }
+    }
+
+    #[maybe_async::async_impl]
+    /// Read the next record
+    pub async fn next(&mut self) -> Option<Result<Record, LineParseError>> {
+        self.next_record().await
+    }
+
+    /// Transform all remaining records and write them
+    ///
+    /// Each record is passed to the `visitor` and written with
+    /// `writer` unless the visitor drops it. Only one record is kept
+    /// in memory at a time. The header and footer are written by the
+    /// `writer` itself, which still has to be finished afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::event::Particle;
+    /// use hepmc2::record::{RecordReader, Visitor};
+    /// use hepmc2::Writer;
+    ///
+    /// // Remove the particle masses
+    /// struct Massless;
+    ///
+    /// impl Visitor for Massless {
+    ///     fn on_particle(&mut self, particle: &mut Particle) -> bool {
+    ///         particle.m = 0.;
+    ///         true
+    ///     }
+    /// }
+    ///
+    /// let input = b"E 0 -1 0 0 0 0 0 1 0 0 0 0
+    /// V -1 0 0 0 0 0 0 1 0
+    /// P 1 22 0 0 1 1 0.5 1 0 0 0 0
+    /// ";
+    /// let mut records = RecordReader::from(input.as_slice());
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::new(&mut output)?;
+    /// records.visit(&mut Massless, &mut writer)?;
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::sync_impl]
+    pub fn visit<V, W>(
+        &mut self,
+        visitor: &mut V,
+        writer: &mut Writer<W>,
+    ) -> Result<(), LineParseError>
+    where
+        V: Visitor + ?Sized,
+        W: std::io::Write,
+    {
+        while let Some(record) = self.next_record() {
+            let mut record = record?;
+            if visitor.on_record(&mut record) {
+                let counts = record_counts(&self.line);
+                writer
+                    .write_record(&record, counts)
+                    .map_err(|err| self.write_error(err))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Transform all remaining records and write them
+    ///
+    /// Each record is passed to the `visitor` and written with
+    /// `writer` unless the visitor drops it. Only one record is kept
+    /// in memory at a time. The header and footer are written by the
+    /// `writer` itself, which still has to be finished afterwards.
+    #[maybe_async::async_impl]
+    pub async fn visit<V, W>(
+        &mut self,
+        visitor: &mut V,
+        writer: &mut Writer<W>,
+    ) -> Result<(), LineParseError>
+    where
+        V: Visitor + ?Sized,
+        W: tokio::io::AsyncWriteExt + Unpin,
+    {
+        while let Some(record) = self.next_record().await {
+            let mut record = record?;
+            if visitor.on_record(&mut record) {
+                let counts = record_counts(&self.line);
+                writer
+                    .write_record(&record, counts)
+                    .await
+                    .map_err(|err| self.write_error(err))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_error(&self, err: std::io::Error) -> LineParseError {
+        LineParseError::new(err.into(), self.line.clone(), self.line_nr)
+    }
+}
+
+/// The vertex and particle counts in a successfully parsed line
+fn record_counts(line: &str) -> RecordCounts {
+    let mut fields = line.split_ascii_whitespace().skip(1);
+    let mut next = |n| {
+        fields
+            .nth(n)
+            .and_then(|field| field.parse().ok())
+            .unwrap_or_default()
+    };
+    match line.as_bytes().first() {
+        Some(b'E') => RecordCounts {
+            vertices: next(7),
+            ..Default::default()
+        },
+        Some(b'V') => RecordCounts {
+            particles_in: next(6),
+            particles_out: next(0),
+            ..Default::default()
+        },
+        _ => RecordCounts::default(),
+    }
+}
+
+#[maybe_async::sync_impl]
+impl<T: std::io::BufRead> Iterator for RecordReader<T> {
+    type Item = Result<Record, LineParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record()
+    }
+}
@@ -0,0 +1,208 @@
+//! Summary statistics for event samples
+use std::collections::BTreeMap;
+
+use crate::event::{CrossSection, Event};
+
+/// Event count, weight sums, and cross section of an event sample
+///
+/// The weight of an event is its first weight, or 1 if it has no
+/// weights.
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```no_run")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::Reader;
+///
+/// let input = std::io::BufReader::new(std::fs::File::open("events.hepmc2")?);
+/// let stats = Reader::from(input).statistics()?;
+/// if let Some(mean) = stats.mean_weight() {
+///     println!("Mean weight: {mean}");
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct Statistics {
+    /// Number of events
+    pub n_events: usize,
+    /// Sum of the event weights
+    pub sum_weights: f64,
+    /// Sum of the squared event weights
+    pub sum_weights_sq: f64,
+    /// Cross section of the last event
+    pub cross_section: Option<CrossSection>,
+}
+
+impl Statistics {
+    /// Construct empty `Statistics`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event
+    pub fn observe(&mut self, event: &Event) {
+        add_weight(
+            &mut self.n_events,
+            &mut self.sum_weights,
+            &mut self.sum_weights_sq,
+            weight(event),
+        );
+        self.cross_section = Some(event.xs);
+    }
+
+    /// Mean event weight
+    ///
+    /// Returns `None` if there are no events.
+    pub fn mean_weight(&self) -> Option<f64> {
+        mean_weight(self.n_events, self.sum_weights)
+    }
+
+    /// Statistical error of the mean event weight
+    ///
+    /// This is the standard deviation of the weights divided by the
+    /// square root of the number of events. Returns `None` if there
+    /// are fewer than two events.
+    pub fn weight_error(&self) -> Option<f64> {
+        weight_error(self.n_events, self.sum_weights, self.sum_weights_sq)
+    }
+}
+
+impl<'a> Extend<&'a Event> for Statistics {
+    fn extend<I: IntoIterator<Item = &'a Event>>(&mut self, events: I) {
+        for event in events {
+            self.observe(event);
+        }
+    }
+}
+
+/// Number of events and sums of their weights
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct ProcessStats {
+    /// Number of events
+    pub count: usize,
+    /// Sum of the event weights
+    pub sum_weights: f64,
+    /// Sum of the squared event weights
+    pub sum_weights_sq: f64,
+}
+
+impl ProcessStats {
+    fn add(&mut self, weight: f64) {
+        add_weight(
+            &mut self.count,
+            &mut self.sum_weights,
+            &mut self.sum_weights_sq,
+            weight,
+        );
+    }
+
+    /// Mean event weight, see [Statistics::mean_weight]
+    pub fn mean_weight(&self) -> Option<f64> {
+        mean_weight(self.count, self.sum_weights)
+    }
+
+    /// Statistical error of the mean event weight, see
+    /// [Statistics::weight_error]
+    pub fn weight_error(&self) -> Option<f64> {
+        weight_error(self.count, self.sum_weights, self.sum_weights_sq)
+    }
+}
+
+/// Statistics for each signal process
+///
+/// Events are grouped by their
+/// [signal_process_id](Event::signal_process_id). The weight of an
+/// event is its first weight, or 1 if it has no weights.
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```no_run")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::stats::ProcessAccumulator;
+/// use hepmc2::Reader;
+///
+/// let input = std::io::BufReader::new(std::fs::File::open("events.hepmc2")?);
+/// let mut acc = ProcessAccumulator::new();
+/// for event in Reader::from(input) {
+///     acc.add(&event?);
+/// }
+/// for (id, stats) in acc.iter() {
+///     println!("Process {id}: {} events", stats.count);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProcessAccumulator {
+    stats: BTreeMap<i32, ProcessStats>,
+}
+
+impl ProcessAccumulator {
+    /// Construct an empty `ProcessAccumulator`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event
+    pub fn add(&mut self, event: &Event) {
+        self.stats
+            .entry(event.signal_process_id)
+            .or_default()
+            .add(weight(event));
+    }
+
+    /// The statistics for the process with the given id
+    pub fn get(&self, signal_process_id: i32) -> Option<&ProcessStats> {
+        self.stats.get(&signal_process_id)
+    }
+
+    /// Iterator over process ids and their statistics
+    ///
+    /// The processes are ordered by their id.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &ProcessStats)> {
+        self.stats.iter().map(|(id, stats)| (*id, stats))
+    }
+}
+
+impl<'a> Extend<&'a Event> for ProcessAccumulator {
+    fn extend<I: IntoIterator<Item = &'a Event>>(&mut self, events: I) {
+        for event in events {
+            self.add(event);
+        }
+    }
+}
+
+impl IntoIterator for ProcessAccumulator {
+    type Item = (i32, ProcessStats);
+    type IntoIter = std::collections::btree_map::IntoIter<i32, ProcessStats>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.stats.into_iter()
+    }
+}
+
+fn weight(event: &Event) -> f64 {
+    event.weights.first().copied().unwrap_or(1.)
+}
+
+fn add_weight(count: &mut usize, sum: &mut f64, sum_sq: &mut f64, weight: f64) {
+    *count += 1;
+    *sum += weight;
+    *sum_sq += weight * weight;
+}
+
+fn mean_weight(count: usize, sum: f64) -> Option<f64> {
+    if count == 0 {
+        return None;
+    }
+    Some(sum / count as f64)
+}
+
+fn weight_error(count: usize, sum: f64, sum_sq: f64) -> Option<f64> {
+    if count < 2 {
+        return None;
+    }
+    let n = count as f64;
+    let mean = sum / n;
+    let var = (sum_sq / n - mean * mean).max(0.);
+    Some((var / (n - 1.)).sqrt())
+}
@@ -0,0 +1,210 @@
+//! Writing with a background IO thread
+//!
+//! This module is only available with the `sync` feature.
+use std::borrow::Borrow;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::event::Event;
+use crate::writer::{Writer, WriterBuilder};
+
+use log::error;
+
+/// Maximum number of formatted events waiting to be written
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Writer that writes to the stream on a background thread
+///
+/// Events are formatted on the calling thread, like with a
+/// [Writer]. The formatted output is then passed
+/// through a bounded channel to a background thread, which does the
+/// actual writing. This allows formatting the next events while the
+/// previous ones are being written.
+///
+/// A `ThreadedWriter` should always be finished with
+/// [finish](Self::finish), which waits for the background thread and
+/// returns the stream. If the `ThreadedWriter` is dropped instead,
+/// errors can only be logged.
+///
+/// # Example
+///
+/// ```
+/// use hepmc2::event::Event;
+/// use hepmc2::threaded::ThreadedWriter;
+///
+/// let mut writer = ThreadedWriter::new(Vec::new())?;
+/// writer.write(Event::default())?;
+/// // always call finish at the end
+/// let output = writer.finish()?;
+/// assert!(output.starts_with(b"HepMC::Version"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+#[must_use = "a `ThreadedWriter` should be finished with `finish`"]
+pub struct ThreadedWriter<T> {
+    writer: Option<Writer<ChannelStream>>,
+    thread: Option<JoinHandle<Result<T, io::Error>>>,
+}
+
+impl<T: Write + Send + 'static> ThreadedWriter<T> {
+    /// Construct a new `ThreadedWriter`
+    ///
+    /// This starts the background thread and writes the mandatory
+    /// HepMC header, which may fail.
+    pub fn new(stream: T) -> Result<Self, io::Error> {
+        Self::with_builder(stream, WriterBuilder::new())
+    }
+
+    /// Construct a new `ThreadedWriter` with the given settings
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hepmc2::threaded::ThreadedWriter;
+    /// use hepmc2::writer::WriterBuilder;
+    ///
+    /// let builder = WriterBuilder::new().precision(17);
+    /// let writer = ThreadedWriter::with_builder(Vec::new(), builder)?;
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_builder(
+        stream: T,
+        builder: WriterBuilder,
+    ) -> Result<Self, io::Error> {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (recycle, recycled) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let thread =
+            thread::spawn(move || write_chunks(stream, receiver, recycle));
+        let stream = ChannelStream { sender, recycled };
+        let mut res = Self {
+            writer: None,
+            thread: Some(thread),
+        };
+        match builder.build(stream) {
+            Ok(writer) => {
+                res.writer = Some(writer);
+                Ok(res)
+            }
+            Err(err) => Err(res.thread_error(err)),
+        }
+    }
+
+    /// Write an event
+    ///
+    /// This returns as soon as the event has been formatted and
+    /// passed to the background thread. Errors from writing to the
+    /// stream are reported by a later call.
+    pub fn write<E: Borrow<Event>>(
+        &mut self,
+        event: E,
+    ) -> Result<(), io::Error> {
+        let res = self.writer.as_mut().unwrap().write(event);
+        res.map_err(|err| self.thread_error(err))
+    }
+
+    /// Write all events from an iterator
+    ///
+    /// Returns the number of events written. See
+    /// [Writer::write_all].
+    pub fn write_all<I>(&mut self, events: I) -> Result<usize, io::Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Event>,
+    {
+        let mut written = 0;
+        for event in events {
+            self.write(event)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Finish writing
+    ///
+    /// This writes the footer, waits until the background thread has
+    /// written and flushed everything, and returns the stream.
+    pub fn finish(mut self) -> Result<T, io::Error> {
+        let res = self.writer.take().unwrap().finish();
+        let stream = match self.thread.take() {
+            Some(thread) => join(thread),
+            None => Err(thread_stopped()),
+        };
+        match (res, stream) {
+            (_, Err(err)) | (Err(err), Ok(_)) => Err(err),
+            (Ok(()), Ok(stream)) => Ok(stream),
+        }
+    }
+
+    /// Replace an error from a stopped background thread by its cause
+    fn thread_error(&mut self, err: io::Error) -> io::Error {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            return err;
+        }
+        match self.thread.take().map(join) {
+            Some(Err(thread_err)) => thread_err,
+            _ => err,
+        }
+    }
+}
+
+impl<T> Drop for ThreadedWriter<T> {
+    fn drop(&mut self) {
+        // this finishes the inner writer and stops the background thread
+        drop(self.writer.take());
+        if let Some(Err(err)) = self.thread.take().map(join) {
+            error!("Error in background writer thread: {err}");
+        }
+    }
+}
+
+/// Stream that passes everything on to the background thread
+#[derive(Debug)]
+struct ChannelStream {
+    sender: SyncSender<Vec<u8>>,
+    recycled: Receiver<Vec<u8>>,
+}
+
+impl Write for ChannelStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        let mut chunk = self.recycled.try_recv().unwrap_or_default();
+        chunk.clear();
+        chunk.extend_from_slice(buf);
+        self.sender.send(chunk).map_err(|_| thread_stopped())?;
+        Ok(buf.len())
+    }
+
+    // the stream is flushed by the background thread once it is done
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+/// Write all received chunks, sending the emptied buffers back
+fn write_chunks<T: Write>(
+    mut stream: T,
+    chunks: Receiver<Vec<u8>>,
+    recycle: SyncSender<Vec<u8>>,
+) -> Result<T, io::Error> {
+    for chunk in chunks {
+        stream.write_all(&chunk)?;
+        // if there are enough spare buffers, just drop this one
+        let _ = recycle.try_send(chunk);
+    }
+    stream.flush()?;
+    Ok(stream)
+}
+
+fn thread_stopped() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "background writer thread stopped",
+    )
+}
+
+fn join<T>(thread: JoinHandle<Result<T, io::Error>>) -> Result<T, io::Error> {
+    thread
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
@@ -1,12 +1,21 @@
+use std::borrow::Borrow;
 use std::default::Default;
-use std::fmt::Display;
+use std::fmt::{self, Display};
 use std::io;
 use std::mem::take;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{ready, Context, Poll};
 
+use crate::compression::{Compression, Compressor};
 use crate::event::*;
+#[cfg(feature = "sync")]
+use crate::ignored::Ignored;
+use crate::record::Record;
 
 use hepmc2_macros::write_bound;
-use log::error;
+use log::{error, warn};
 
 const DEFAULT_HEADER: &str = "HepMC::Version 2.06.09
 HepMC::IO_GenEvent-START_EVENT_LISTING
@@ -14,6 +23,20 @@ HepMC::IO_GenEvent-START_EVENT_LISTING
 
 const DEFAULT_FOOTER: &[u8] = b"HepMC::IO_GenEvent-END_EVENT_LISTING\n";
 
+#[cfg(feature = "sync")]
+type FileStream = std::io::BufWriter<std::fs::File>;
+#[cfg(feature = "tokio")]
+type FileStream = tokio::io::BufWriter<tokio::fs::File>;
+
+/// Default precision of HepMC
+///
+/// HepMC writes floating-point numbers like `printf("%.16e", x)`,
+/// which corresponds to 17 significant digits. Passing this to
+/// [Writer::set_precision] or [WriterBuilder::precision] reproduces
+/// the formatting of files written by HepMC, which allows
+/// byte-for-byte comparisons with reference files.
+pub const HEPMC_PRECISION: usize = 17;
+
 /// Write formatted data into a buffer.
 ///
 /// If the `sync` feature is enabled this just passes the arguments to
@@ -32,15 +55,73 @@ macro_rules! maybe_write {
         $dst.write_all(::std::format!($fmt, $($arg)*).as_bytes()).await?;
     }};
 }
+pub(crate) use maybe_write;
 
 /// Writer for the HepMC2 format
+///
+/// A `Writer` should always be finished with [finish](Self::finish),
+/// which is the only way to learn whether writing the footer and
+/// flushing the stream succeeded. If the `Writer` is dropped
+/// instead, it still tries to write the footer, but errors can only
+/// be logged. See [set_on_unfinished](Self::set_on_unfinished) and
+/// [strict](Self::strict) for alternatives.
 #[write_bound]
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[must_use = "a `Writer` should be finished with `finish`"]
 pub struct Writer<T> {
     stream: T,
     finished: bool,
+    skip_footer: bool,
+    precision: Option<usize>,
+    always_write_weight_names: bool,
+    omit_zero_pdf_ids: bool,
+    non_finite: NonFinite,
+    on_unfinished: OnUnfinished,
+    #[cfg(feature = "sync")]
+    finish_stream: Ignored<Option<FinishFn<T>>>,
+    /// Formatted output that has not been written to the stream yet
+    ///
+    /// Events are formatted into this buffer first, so that each
+    /// event takes a single write to the stream.
+    buf: Vec<u8>,
+    /// Number of bytes at the start of `buf` that have been written
+    #[cfg(feature = "tokio")]
+    buf_written: usize,
+    #[cfg(feature = "tokio")]
+    footer_queued: bool,
 }
 
+/// How a [Writer] handles floating-point numbers that are not finite
+///
+/// HepMC3 readers reject NaN and infinite values.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub enum NonFinite {
+    /// Return an error without writing the event
+    #[default]
+    Error,
+    /// Log a warning and write `0` instead
+    Substitute,
+}
+
+/// What to do when a [Writer] is dropped before it is finished
+///
+/// In all cases, the writer tries to write the footer before it is
+/// dropped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub enum OnUnfinished {
+    /// Log an error
+    #[default]
+    Log,
+    /// Do nothing
+    Silent,
+    /// Panic, unless the thread is already panicking
+    Panic,
+}
+
+/// Custom function for finishing the output stream
+#[cfg(feature = "sync")]
+type FinishFn<T> = fn(&mut T) -> Result<(), io::Error>;
+
 #[write_bound]
 impl<T: Default> Writer<T> {
     /// Retrieve the underlying writer
@@ -125,17 +206,103 @@ impl<T> Writer<T> {
         stream: T,
         header: U,
     ) -> Result<Self, io::Error> {
-        let mut writer = Self {
-            stream,
-            finished: false,
+        let builder = WriterBuilder::new().header(header);
+        Self::with_builder(stream, builder).await
+    }
+
+    /// Construct new `Writer` that panics if it is not finished
+    ///
+    /// In debug builds, dropping the returned `Writer` without
+    /// calling [finish](Self::finish) panics after trying to write the
+    /// footer. This catches missing calls to `finish`, which would
+    /// hide errors when writing the footer or flushing the stream. In
+    /// release builds, the `Writer` logs an error instead, like one
+    /// constructed with [new](Self::new). See also
+    /// [set_on_unfinished](Self::set_on_unfinished).
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    ///
+    /// let mut output = Vec::new();
+    /// let writer = Writer::strict(&mut output)?;
+    /// // dropping `writer` here would panic in debug builds
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn strict(stream: T) -> Result<Self, io::Error> {
+        let on_unfinished = if cfg!(debug_assertions) {
+            OnUnfinished::Panic
+        } else {
+            OnUnfinished::Log
         };
-        writer.write_header(header).await?;
-        Ok(writer)
+        let builder = WriterBuilder::new().on_unfinished(on_unfinished);
+        Self::with_builder(stream, builder).await
+    }
+
+    /// Construct new `Writer` that never writes a footer
+    ///
+    /// The footer is neither written by [finish](Self::finish) nor
+    /// when the `Writer` is dropped. This is useful for producing
+    /// chunks of output that are concatenated later. The output on
+    /// its own is not a valid HepMC file.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::without_footer(&mut output)?;
+    /// writer.finish()?;
+    /// assert!(!String::from_utf8(output)?.contains("END_EVENT_LISTING"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn without_footer(stream: T) -> Result<Self, io::Error> {
+        let builder = WriterBuilder::new().write_footer(false);
+        Self::with_builder(stream, builder).await
+    }
+
+    /// Construct new `Writer` that continues existing output
+    ///
+    /// No header is written, so events are appended directly to
+    /// `stream`. The footer is written as usual. Together with
+    /// [without_footer](Self::without_footer), this allows writing a
+    /// single valid file in several steps.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    /// use hepmc2::event::Event;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::without_footer(&mut output)?;
+    /// writer.write(&Event::default())?;
+    /// writer.finish()?;
+    ///
+    /// let mut writer = Writer::appending(&mut output)?;
+    /// writer.write(&Event::default())?;
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn appending(stream: T) -> Result<Self, io::Error> {
+        let builder = WriterBuilder::new().header("");
+        Self::with_builder(stream, builder).await
     }
 
     /// Finish writing, consuming the `Writer`
     ///
-    /// This tries to write the mandatory HepMC footer, which may fail.
+    /// This tries to write the mandatory HepMC footer and then
+    /// flushes (or shuts down, for the async API) the underlying
+    /// stream, which may fail.
     ///
     /// # Example
     ///
@@ -172,6 +339,8 @@ impl<T> Writer<T> {
 
     /// Write an event
     ///
+    /// The event can be passed either by reference or by value.
+    ///
     /// # Example
     ///
     /// ## Sync
@@ -207,28 +376,201 @@ impl<T> Writer<T> {
     /// # })
     /// ```
     #[maybe_async::maybe_async]
-    pub async fn write(&mut self, event: &Event) -> Result<(), io::Error> {
-        self.write_event_line(event).await?;
-        if !event.weight_names.is_empty() {
-            self.write_weight_names_line(&event.weight_names).await?;
-        }
-        self.write_unit_line(event).await?;
-        self.write_cross_section_line(&event.xs).await?;
-        self.write_pdf_info_line(&event.pdf_info).await?;
-        if let Some(hi) = event.heavy_ion_info {
-            self.write_heavy_ion_info_line(&hi).await?;
+    pub async fn write<E: Borrow<Event>>(
+        &mut self,
+        event: E,
+    ) -> Result<(), io::Error> {
+        let event = event.borrow();
+        let format = self.format();
+        #[cfg(feature = "sync")]
+        {
+            self.buf.clear();
+            format.write_event(&mut self.buf, event)?;
+            self.stream.write_all(&self.buf)
         }
-        for vertex in &event.vertices {
-            self.write_vertex_line(vertex).await?;
-            let particles = vertex
-                .particles_in
-                .iter()
-                .chain(vertex.particles_out.iter());
-            for particle in particles {
-                self.write_particle_line(particle).await?;
-            }
+        #[cfg(feature = "tokio")]
+        {
+            format.write_event(&mut self.buf, event)?;
+            std::future::poll_fn(|cx| self.poll_write_buf(cx)).await
+        }
+    }
+
+    /// Write a single record
+    #[maybe_async::maybe_async]
+    pub(crate) async fn write_record(
+        &mut self,
+        record: &Record,
+        counts: RecordCounts,
+    ) -> Result<(), io::Error> {
+        let format = self.format();
+        #[cfg(feature = "sync")]
+        {
+            self.buf.clear();
+            format.write_record(&mut self.buf, record, counts)?;
+            self.stream.write_all(&self.buf)
+        }
+        #[cfg(feature = "tokio")]
+        {
+            format.write_record(&mut self.buf, record, counts)?;
+            std::future::poll_fn(|cx| self.poll_write_buf(cx)).await
+        }
+    }
+
+    /// Write several events, returning the number of events written
+    ///
+    /// Like for [write](Self::write), the events can be passed either
+    /// by reference or by value.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    /// use hepmc2::event::Event;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::new(&mut output)?;
+    /// let events = vec![Event::default(); 2];
+    /// let written = writer.write_all(&events)?;
+    /// assert_eq!(written, 2);
+    /// // always call finish at the end
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn write_all<I>(&mut self, events: I) -> Result<usize, io::Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Event>,
+    {
+        let mut written = 0;
+        for event in events {
+            self.write(event).await?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Set the number of significant digits for floating-point numbers
+    ///
+    /// By default (`None`), numbers are written in the shortest
+    /// representation that can be read back without loss of
+    /// precision. With `Some(digits)`, numbers are instead written in
+    /// scientific notation with a fixed number of significant digits,
+    /// like `printf("%.*e", digits - 1, x)` in C. Exact zeros are
+    /// always written as `0`.
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    ///
+    /// use hepmc2::writer::HEPMC_PRECISION;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::new(&mut output)?;
+    /// // same format as `%.16e`
+    /// writer.set_precision(Some(HEPMC_PRECISION));
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_precision(&mut self, digits: Option<usize>) {
+        self.precision = digits.map(|d| d.max(1));
+    }
+
+    /// The number of significant digits for floating-point numbers
+    ///
+    /// See [set_precision](Self::set_precision).
+    pub fn precision(&self) -> Option<usize> {
+        self.precision
+    }
+
+    /// Set whether to write a weight names line for every event
+    ///
+    /// By default, the `N` line with the weight names is only written
+    /// for events that have weight names. Some readers expect it for
+    /// every event, which can be enforced by setting this to `true`.
+    /// For events without weight names, the line is then `N 0`.
+    pub fn set_always_write_weight_names(&mut self, always: bool) {
+        self.always_write_weight_names = always;
+    }
+
+    /// Whether a weight names line is written for every event
+    ///
+    /// See
+    /// [set_always_write_weight_names](Self::set_always_write_weight_names).
+    pub fn always_write_weight_names(&self) -> bool {
+        self.always_write_weight_names
+    }
+
+    /// Set whether to omit the pdf ids if they are both zero
+    ///
+    /// By default, the pdf info (`F`) line always contains the two
+    /// LHAPDF ids. Older HepMC versions write the line without them,
+    /// which can be reproduced by setting this to `true`.
+    pub fn set_omit_zero_pdf_ids(&mut self, omit: bool) {
+        self.omit_zero_pdf_ids = omit;
+    }
+
+    /// Whether the pdf ids are omitted if they are both zero
+    ///
+    /// See [set_omit_zero_pdf_ids](Self::set_omit_zero_pdf_ids).
+    pub fn omit_zero_pdf_ids(&self) -> bool {
+        self.omit_zero_pdf_ids
+    }
+
+    /// Set how to handle floating-point numbers that are not finite
+    ///
+    /// By default, trying to write an event containing a NaN or
+    /// infinite value results in an error. See [NonFinite] for the
+    /// alternatives.
+    pub fn set_non_finite(&mut self, policy: NonFinite) {
+        self.non_finite = policy;
+    }
+
+    /// How floating-point numbers that are not finite are handled
+    ///
+    /// See [set_non_finite](Self::set_non_finite).
+    pub fn non_finite(&self) -> NonFinite {
+        self.non_finite
+    }
+
+    /// Set what to do when the writer is dropped before it is finished
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::{OnUnfinished, Writer};
+    ///
+    /// let mut output = Vec::new();
+    /// {
+    ///     let mut writer = Writer::new(&mut output)?;
+    ///     writer.set_on_unfinished(OnUnfinished::Silent);
+    ///     // dropped without calling finish
+    /// }
+    /// assert!(output.ends_with(b"END_EVENT_LISTING\n"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_on_unfinished(&mut self, policy: OnUnfinished) {
+        self.on_unfinished = policy;
+    }
+
+    /// What to do when the writer is dropped before it is finished
+    ///
+    /// See [set_on_unfinished](Self::set_on_unfinished).
+    pub fn on_unfinished(&self) -> OnUnfinished {
+        self.on_unfinished
+    }
+
+    fn format(&self) -> EventFormat {
+        EventFormat {
+            precision: self.precision,
+            always_write_weight_names: self.always_write_weight_names,
+            omit_zero_pdf_ids: self.omit_zero_pdf_ids,
+            non_finite: self.non_finite,
         }
-        Ok(())
     }
 
     #[maybe_async::maybe_async]
@@ -237,10 +579,83 @@ impl<T> Writer<T> {
     }
 
     #[maybe_async::maybe_async]
+    async fn with_builder(
+        stream: T,
+        builder: WriterBuilder,
+    ) -> Result<Self, io::Error> {
+        let mut writer = Self {
+            stream,
+            finished: false,
+            skip_footer: !builder.write_footer,
+            precision: builder.precision,
+            always_write_weight_names: builder.always_write_weight_names,
+            omit_zero_pdf_ids: builder.omit_zero_pdf_ids,
+            non_finite: builder.non_finite,
+            on_unfinished: builder.on_unfinished,
+            #[cfg(feature = "sync")]
+            finish_stream: Ignored(None),
+            buf: Vec::new(),
+            #[cfg(feature = "tokio")]
+            buf_written: 0,
+            #[cfg(feature = "tokio")]
+            footer_queued: false,
+        };
+        writer.write_header(builder.header).await?;
+        Ok(writer)
+    }
+
+    #[maybe_async::sync_impl]
+    fn ref_finish(&mut self) -> Result<(), std::io::Error> {
+        if !self.skip_footer {
+            self.stream.write_all(DEFAULT_FOOTER)?;
+        }
+        self.finished = true;
+        if let Some(finish_stream) = *self.finish_stream {
+            return finish_stream(&mut self.stream);
+        }
+        self.stream.flush()
+    }
+
+    #[maybe_async::async_impl]
     async fn ref_finish(&mut self) -> Result<(), std::io::Error> {
-        self.stream.write_all(DEFAULT_FOOTER).await?;
+        std::future::poll_fn(|cx| self.poll_finish(cx)).await
+    }
+
+    /// Write the buffered output to the stream
+    #[cfg(feature = "tokio")]
+    fn poll_write_buf(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        while self.buf_written < self.buf.len() {
+            let buf = &self.buf[self.buf_written..];
+            let stream = Pin::new(&mut self.stream);
+            let nbytes = ready!(stream.poll_write(cx, buf))?;
+            if nbytes == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.buf_written += nbytes;
+        }
+        self.buf.clear();
+        self.buf_written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write the remaining output and the footer and shut down the stream
+    #[cfg(feature = "tokio")]
+    fn poll_finish(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        if !self.footer_queued {
+            if !self.skip_footer {
+                self.buf.extend_from_slice(DEFAULT_FOOTER);
+            }
+            self.footer_queued = true;
+        }
+        ready!(self.poll_write_buf(cx))?;
         self.finished = true;
-        Ok(())
+        Pin::new(&mut self.stream).poll_shutdown(cx)
     }
 
     #[maybe_async::maybe_async]
@@ -251,154 +666,253 @@ impl<T> Writer<T> {
         maybe_write!(self.stream, "{}", header);
         Ok(())
     }
+}
 
-    #[maybe_async::maybe_async]
-    async fn write_event_line(
-        &mut self,
+/// Numbers of vertices and particles written in a record line
+///
+/// These are not part of a [Record], since the vertices and particles
+/// are separate records.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct RecordCounts {
+    /// Number of vertices in an `E` line
+    pub(crate) vertices: usize,
+    /// Number of orphan incoming particles in a `V` line
+    pub(crate) particles_in: usize,
+    /// Number of outgoing particles in a `V` line
+    pub(crate) particles_out: usize,
+}
+
+/// Formatting of events in the HepMC2 format
+#[derive(Debug, Copy, Clone)]
+struct EventFormat {
+    precision: Option<usize>,
+    always_write_weight_names: bool,
+    omit_zero_pdf_ids: bool,
+    non_finite: NonFinite,
+}
+
+impl EventFormat {
+    fn write_event<W: io::Write>(
+        &self,
+        out: &mut W,
         event: &Event,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "E {} {} {} {} {} {} {} {} 0 0 {}",
+        if self.non_finite == NonFinite::Error {
+            check_finite(event)?;
+        }
+        self.write_event_line(out, event, event.vertices.len())?;
+        let weight_names = &event.weight_names;
+        if self.always_write_weight_names || !weight_names.is_empty() {
+            self.write_weight_names_line(out, weight_names)?;
+        }
+        self.write_unit_line(out, event.energy_unit, event.length_unit)?;
+        self.write_cross_section_line(out, &event.xs)?;
+        self.write_pdf_info_line(out, &event.pdf_info)?;
+        if let Some(hi) = event.heavy_ion_info {
+            self.write_heavy_ion_info_line(out, &hi)?;
+        }
+        for vertex in &event.vertices {
+            let nin = vertex.particles_in.len();
+            let nout = vertex.particles_out.len();
+            self.write_vertex_line(out, vertex, nin, nout)?;
+            let particles = vertex
+                .particles_in
+                .iter()
+                .chain(vertex.particles_out.iter());
+            for particle in particles {
+                self.write_particle_line(out, particle)?;
+            }
+        }
+        for line in &event.raw_extra {
+            out.write_all(line.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_record<W: io::Write>(
+        &self,
+        out: &mut W,
+        record: &Record,
+        counts: RecordCounts,
+    ) -> Result<(), io::Error> {
+        if self.non_finite == NonFinite::Error {
+            check_finite_record(record)?;
+        }
+        match record {
+            Record::EventHeader(event) => {
+                self.write_event_line(out, event, counts.vertices)
+            }
+            Record::Vertex(vertex) => self.write_vertex_line(
+                out,
+                vertex,
+                counts.particles_in,
+                counts.particles_out,
+            ),
+            Record::Particle(particle) => {
+                self.write_particle_line(out, particle)
+            }
+            Record::Units(energy, length) => {
+                self.write_unit_line(out, *energy, *length)
+            }
+            Record::CrossSection(xs) => self.write_cross_section_line(out, xs),
+            Record::PdfInfo(pdf) => self.write_pdf_info_line(out, pdf),
+            Record::HeavyIon(hi) => self.write_heavy_ion_info_line(out, hi),
+            Record::WeightNames(names) => {
+                self.write_weight_names_line(out, names)
+            }
+            Record::Unknown(line) => writeln!(out, "{line}"),
+        }
+    }
+
+    fn write_event_line<W: io::Write>(
+        &self,
+        out: &mut W,
+        event: &Event,
+        nvertices: usize,
+    ) -> Result<(), io::Error> {
+        write!(
+            out,
+            "E {} {} {} {} {} {} {} {} {} {} {}",
             event.number,
             event.mpi,
-            ryu::Buffer::new().format(event.scale),
-            ryu::Buffer::new().format(event.alpha_qcd),
-            ryu::Buffer::new().format(event.alpha_qed),
+            self.float(event.scale),
+            self.float(event.alpha_qcd),
+            self.float(event.alpha_qed),
             event.signal_process_id,
             event.signal_process_vertex,
-            event.vertices.len(),
+            nvertices,
+            event.beam_barcodes[0],
+            event.beam_barcodes[1],
             event.random_states.len()
-        );
+        )?;
         for state in &event.random_states {
-            maybe_write!(self.stream, " {}", state);
+            write!(out, " {}", state)?;
         }
-        maybe_write!(self.stream, " {}", event.weights.len());
-        let mut buffer = ryu::Buffer::new();
+        write!(out, " {}", event.weights.len())?;
         for weight in &event.weights {
-            maybe_write!(self.stream, " {}", buffer.format(*weight));
+            write!(out, " {}", self.float(*weight))?;
         }
-        self.stream.write_all(b"\n").await
+        out.write_all(b"\n")
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_vertex_line(
-        &mut self,
+    fn write_vertex_line<W: io::Write>(
+        &self,
+        out: &mut W,
         vertex: &Vertex,
+        nin: usize,
+        nout: usize,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "V {} {} {} {} {} {} 0 {} {}",
+        write!(
+            out,
+            "V {} {} {} {} {} {} {} {} {}",
             vertex.barcode,
             vertex.status,
-            ryu::Buffer::new().format(vertex.x),
-            ryu::Buffer::new().format(vertex.y),
-            ryu::Buffer::new().format(vertex.z),
-            ryu::Buffer::new().format(vertex.t),
-            vertex.particles_in.len() + vertex.particles_out.len(),
+            self.float(vertex.x),
+            self.float(vertex.y),
+            self.float(vertex.z),
+            self.float(vertex.t),
+            nin,
+            nout,
             vertex.weights.len()
-        );
+        )?;
         for weight in &vertex.weights {
-            maybe_write!(self.stream, " {}", weight);
+            write!(out, " {}", self.float(*weight))?;
         }
-        self.stream.write_all(b"\n").await
+        out.write_all(b"\n")
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_particle_line(
-        &mut self,
+    fn write_particle_line<W: io::Write>(
+        &self,
+        out: &mut W,
         particle: &Particle,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "P 0 {} {} {} {} {} {} {} {} {} {} {}",
+        write!(
+            out,
+            "P {} {} {} {} {} {} {} {} {} {} {} {}",
+            particle.barcode,
             particle.id,
-            ryu::Buffer::new().format(particle.p[1]),
-            ryu::Buffer::new().format(particle.p[2]),
-            ryu::Buffer::new().format(particle.p[3]),
-            ryu::Buffer::new().format(particle.p[0]),
-            ryu::Buffer::new().format(particle.m),
+            self.float(particle.p[1]),
+            self.float(particle.p[2]),
+            self.float(particle.p[3]),
+            self.float(particle.p[0]),
+            self.float(particle.m),
             particle.status,
-            ryu::Buffer::new().format(particle.theta),
-            ryu::Buffer::new().format(particle.phi),
+            self.float(particle.theta),
+            self.float(particle.phi),
             particle.end_vtx,
             particle.flows.len()
-        );
+        )?;
         for (idx, val) in &particle.flows {
-            maybe_write!(self.stream, " {} {}", idx, val);
+            write!(out, " {} {}", idx, val)?;
         }
-        self.stream.write_all(b"\n").await
+        out.write_all(b"\n")
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_weight_names_line(
-        &mut self,
+    fn write_weight_names_line<W: io::Write>(
+        &self,
+        out: &mut W,
         names: &[String],
     ) -> Result<(), io::Error> {
-        maybe_write!(self.stream, "N {}", names.len());
+        write!(out, "N {}", names.len())?;
         for name in names {
-            maybe_write!(self.stream, r#" "{}""#, name);
+            write!(out, r#" "{}""#, name)?;
         }
-        self.stream.write_all(b"\n").await
+        out.write_all(b"\n")
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_unit_line(
-        &mut self,
-        event: &Event,
+    fn write_unit_line<W: io::Write>(
+        &self,
+        out: &mut W,
+        energy_unit: EnergyUnit,
+        length_unit: LengthUnit,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "U {:?} {:?}\n",
-            event.energy_unit,
-            event.length_unit
-        );
-        Ok(())
+        writeln!(out, "U {} {}", energy_unit, length_unit)
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_cross_section_line(
-        &mut self,
+    fn write_cross_section_line<W: io::Write>(
+        &self,
+        out: &mut W,
         xs: &CrossSection,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "C {} {}\n",
-            ryu::Buffer::new().format(xs.cross_section),
-            ryu::Buffer::new().format(xs.cross_section_error)
-        );
-        Ok(())
+        writeln!(
+            out,
+            "C {} {}",
+            self.float(xs.cross_section),
+            self.float(xs.cross_section_error)
+        )
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_pdf_info_line(
-        &mut self,
+    fn write_pdf_info_line<W: io::Write>(
+        &self,
+        out: &mut W,
         pdf: &PdfInfo,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "F {} {} {} {} {} {} {} {} {}\n",
+        write!(
+            out,
+            "F {} {} {} {} {} {} {}",
             pdf.parton_id[0],
             pdf.parton_id[1],
-            ryu::Buffer::new().format(pdf.x[0]),
-            ryu::Buffer::new().format(pdf.x[1]),
-            ryu::Buffer::new().format(pdf.scale),
-            ryu::Buffer::new().format(pdf.xf[0]),
-            ryu::Buffer::new().format(pdf.xf[1]),
-            pdf.pdf_id[0],
-            pdf.pdf_id[1],
-        );
-        Ok(())
+            self.float(pdf.x[0]),
+            self.float(pdf.x[1]),
+            self.float(pdf.scale),
+            self.float(pdf.xf[0]),
+            self.float(pdf.xf[1]),
+        )?;
+        if self.omit_zero_pdf_ids && pdf.pdf_id == [0, 0] {
+            return out.write_all(b"\n");
+        }
+        writeln!(out, " {} {}", pdf.pdf_id[0], pdf.pdf_id[1])
     }
 
-    #[maybe_async::maybe_async]
-    async fn write_heavy_ion_info_line(
-        &mut self,
+    fn write_heavy_ion_info_line<W: io::Write>(
+        &self,
+        out: &mut W,
         hi: &HeavyIonInfo,
     ) -> Result<(), io::Error> {
-        maybe_write!(
-            self.stream,
-            "H {} {} {} {} {} {} {} {} {} {} {} {} {}\n",
+        writeln!(
+            out,
+            "H {} {} {} {} {} {} {} {} {} {} {} {} {}",
             hi.ncoll_hard,
             hi.npart_proj,
             hi.npart_targ,
@@ -408,33 +922,495 @@ impl<T> Writer<T> {
             hi.n_nwounded_collisions,
             hi.nwounded_n_collisions,
             hi.nwounded_nwounded_collisions,
-            ryu::Buffer::new().format(hi.impact_parameter),
-            ryu::Buffer::new().format(hi.event_plane_angle),
-            ryu::Buffer::new().format(hi.eccentricity),
-            ryu::Buffer::new().format(hi.sigma_inel_nn),
-        );
+            self.float(hi.impact_parameter),
+            self.float(hi.event_plane_angle),
+            self.float(hi.eccentricity),
+            self.float(hi.sigma_inel_nn),
+        )
+    }
+
+    fn float(&self, value: f64) -> Float {
+        let value = if value.is_finite() {
+            value
+        } else {
+            warn!("Writing non-finite value {value} as 0");
+            0.
+        };
+        Float {
+            value,
+            precision: self.precision,
+        }
+    }
+}
+
+/// Check that all floating-point numbers in `event` are finite
+fn check_finite(event: &Event) -> Result<(), io::Error> {
+    let check = || -> Result<(), f64> {
+        check_event_line(event)?;
+        check_values(&[event.xs.cross_section, event.xs.cross_section_error])?;
+        check_pdf_info(&event.pdf_info)?;
+        if let Some(hi) = &event.heavy_ion_info {
+            check_heavy_ion_info(hi)?;
+        }
+        for vertex in &event.vertices {
+            check_vertex(vertex)?;
+            let particles = vertex
+                .particles_in
+                .iter()
+                .chain(vertex.particles_out.iter());
+            for particle in particles {
+                check_particle(particle)?;
+            }
+        }
         Ok(())
+    };
+    check().map_err(|value| {
+        non_finite_error(format!(
+            "Non-finite value {value} in event {}",
+            event.number
+        ))
+    })
+}
+
+/// Check that all floating-point numbers in `record` are finite
+fn check_finite_record(record: &Record) -> Result<(), io::Error> {
+    let res = match record {
+        Record::EventHeader(event) => return check_finite(event),
+        Record::Vertex(vertex) => check_vertex(vertex),
+        Record::Particle(particle) => check_particle(particle),
+        Record::CrossSection(xs) => {
+            check_values(&[xs.cross_section, xs.cross_section_error])
+        }
+        Record::PdfInfo(pdf) => check_pdf_info(pdf),
+        Record::HeavyIon(hi) => check_heavy_ion_info(hi),
+        Record::Units(..) | Record::WeightNames(_) | Record::Unknown(_) => {
+            Ok(())
+        }
+    };
+    res.map_err(|value| {
+        non_finite_error(format!("Non-finite value {value} in record"))
+    })
+}
+
+fn non_finite_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Return the first non-finite value as error
+fn check_values(values: &[f64]) -> Result<(), f64> {
+    match values.iter().find(|v| !v.is_finite()) {
+        Some(value) => Err(*value),
+        None => Ok(()),
+    }
+}
+
+fn check_event_line(event: &Event) -> Result<(), f64> {
+    check_values(&[event.scale, event.alpha_qcd, event.alpha_qed])?;
+    check_values(&event.weights)
+}
+
+fn check_pdf_info(pdf: &PdfInfo) -> Result<(), f64> {
+    check_values(&[pdf.x[0], pdf.x[1], pdf.scale, pdf.xf[0], pdf.xf[1]])
+}
+
+fn check_heavy_ion_info(hi: &HeavyIonInfo) -> Result<(), f64> {
+    check_values(&[
+        hi.impact_parameter,
+        hi.event_plane_angle,
+        hi.eccentricity,
+        hi.sigma_inel_nn,
+    ])
+}
+
+fn check_vertex(vertex: &Vertex) -> Result<(), f64> {
+    check_values(&[vertex.x, vertex.y, vertex.z, vertex.t])?;
+    check_values(&vertex.weights)
+}
+
+fn check_particle(particle: &Particle) -> Result<(), f64> {
+    check_values(&particle.p.0)?;
+    check_values(&[particle.m, particle.theta, particle.phi])
+}
+
+/// Builder for a [Writer] with custom settings
+///
+/// # Example
+///
+#[cfg_attr(feature = "sync", doc = "```")]
+#[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+/// use hepmc2::writer::WriterBuilder;
+///
+/// let mut output = Vec::new();
+/// let mut writer = WriterBuilder::new()
+///     .header("")
+///     .precision(17)
+///     .build(&mut output)?;
+/// // always call finish at the end
+/// writer.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WriterBuilder {
+    header: String,
+    write_footer: bool,
+    precision: Option<usize>,
+    always_write_weight_names: bool,
+    omit_zero_pdf_ids: bool,
+    non_finite: NonFinite,
+    on_unfinished: OnUnfinished,
+}
+
+impl Default for WriterBuilder {
+    fn default() -> Self {
+        Self {
+            header: DEFAULT_HEADER.to_owned(),
+            write_footer: true,
+            precision: None,
+            always_write_weight_names: false,
+            omit_zero_pdf_ids: false,
+            non_finite: NonFinite::default(),
+            on_unfinished: OnUnfinished::default(),
+        }
+    }
+}
+
+impl WriterBuilder {
+    /// Construct a new `WriterBuilder` with default settings
+    ///
+    /// With the default settings, the built `Writer` is the same as
+    /// one constructed with [Writer::new].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the header written at the start of the output
+    ///
+    /// See [Writer::with_header].
+    pub fn header<U: Display>(mut self, header: U) -> Self {
+        self.header = header.to_string();
+        self
+    }
+
+    /// Set whether the footer is written when the `Writer` is finished
+    pub fn write_footer(mut self, write_footer: bool) -> Self {
+        self.write_footer = write_footer;
+        self
+    }
+
+    /// Set the number of significant digits for floating-point numbers
+    ///
+    /// See [Writer::set_precision].
+    pub fn precision(mut self, digits: usize) -> Self {
+        self.precision = Some(digits.max(1));
+        self
+    }
+
+    /// Set whether to write a weight names line for every event
+    ///
+    /// See [Writer::set_always_write_weight_names].
+    pub fn always_write_weight_names(mut self, always: bool) -> Self {
+        self.always_write_weight_names = always;
+        self
+    }
+
+    /// Set whether to omit the pdf ids if they are both zero
+    ///
+    /// See [Writer::set_omit_zero_pdf_ids].
+    pub fn omit_zero_pdf_ids(mut self, omit: bool) -> Self {
+        self.omit_zero_pdf_ids = omit;
+        self
+    }
+
+    /// Set how to handle floating-point numbers that are not finite
+    ///
+    /// See [Writer::set_non_finite].
+    pub fn non_finite(mut self, policy: NonFinite) -> Self {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Set what to do when the `Writer` is dropped before it is finished
+    ///
+    /// See [Writer::set_on_unfinished].
+    pub fn on_unfinished(mut self, policy: OnUnfinished) -> Self {
+        self.on_unfinished = policy;
+        self
+    }
+
+    /// Construct a new `Writer` with the current settings
+    ///
+    /// This automatically tries to write the header, which may fail.
+    #[maybe_async::sync_impl]
+    pub fn build<T>(self, stream: T) -> Result<Writer<T>, io::Error>
+    where
+        T: io::Write,
+    {
+        Writer::with_builder(stream, self)
+    }
+
+    /// Construct a new `Writer` with the current settings
+    ///
+    /// This automatically tries to write the header, which may fail.
+    #[maybe_async::async_impl]
+    pub async fn build<T>(self, stream: T) -> Result<Writer<T>, io::Error>
+    where
+        T: tokio::io::AsyncWriteExt + Unpin,
+    {
+        Writer::with_builder(stream, self).await
+    }
+}
+
+/// Floating-point number formatted according to the `Writer` settings
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Float {
+    pub(crate) value: f64,
+    pub(crate) precision: Option<usize>,
+}
+
+impl Float {
+    /// Shortest representation that reads back to the same value
+    pub(crate) fn shortest(value: f64) -> Self {
+        Self {
+            value,
+            precision: None,
+        }
+    }
+}
+
+impl Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(digits) = self.precision else {
+            return f.write_str(ryu::Buffer::new().format(self.value));
+        };
+        if self.value == 0. {
+            return f.write_str("0");
+        }
+        if !self.value.is_finite() {
+            return write!(f, "{}", self.value);
+        }
+        // mimic C's `%e`, which has a sign and at least two digits
+        // in the exponent
+        let formatted = format!("{:.*e}", digits - 1, self.value);
+        let (mantissa, exp) = formatted.split_once('e').unwrap();
+        let exp: i32 = exp.parse().unwrap();
+        let sign = if exp < 0 { '-' } else { '+' };
+        write!(f, "{mantissa}e{sign}{:02}", exp.abs())
+    }
+}
+
+#[write_bound]
+impl<T> Writer<Compressor<T>> {
+    /// Construct new `Writer` with compressed output
+    ///
+    /// This automatically tries to write the mandatory HepMC header,
+    /// which may fail. The footer is written to the compressed stream
+    /// before the compression is finished in [finish](Writer::finish).
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(all(feature = "sync", feature = "zstd"), doc = "```")]
+    #[cfg_attr(not(all(feature = "sync", feature = "zstd")), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    /// use hepmc2::compression::Compression;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::with_compression(&mut output, Compression::Zstd)?;
+    /// // always call finish at the end
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn with_compression(
+        stream: T,
+        compression: Compression,
+    ) -> Result<Self, io::Error> {
+        let stream = Compressor::new(stream, compression)?;
+        // with the `tokio` feature, `Compressor` finishes on shutdown
+        #[allow(unused_mut)]
+        let mut writer =
+            Self::with_builder(stream, WriterBuilder::new()).await?;
+        #[cfg(feature = "sync")]
+        {
+            writer.finish_stream = Ignored(Some(Compressor::try_finish));
+        }
+        Ok(writer)
+    }
+}
+
+impl Writer<Compressor<FileStream>> {
+    /// Create the file at `path` and construct a new `Writer` for it
+    ///
+    /// The output is compressed according to the file extension:
+    /// `gz` for gzip, `zst` for zstd, and `xz` for xz. This requires
+    /// the corresponding crate feature, otherwise an error is
+    /// returned. Other extensions result in uncompressed output. See
+    /// also [with_compression](Self::with_compression).
+    ///
+    /// # Example
+    ///
+    #[cfg_attr(feature = "sync", doc = "```no_run")]
+    #[cfg_attr(not(feature = "sync"), doc = "```ignore")]
+    /// use hepmc2::writer::Writer;
+    ///
+    /// let mut writer = Writer::create("events.hepmc2")?;
+    /// // always call finish at the end
+    /// writer.finish()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[maybe_async::maybe_async]
+    pub async fn create<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<Self, io::Error> {
+        let compression = Compression::from_path(path.as_ref())?;
+        #[cfg(feature = "sync")]
+        let file = std::fs::File::create(path)?;
+        #[cfg(feature = "tokio")]
+        let file = tokio::fs::File::create(path).await?;
+        Self::with_compression(FileStream::new(file), compression).await
+    }
+}
+
+/// Writing events from an asynchronous stream
+///
+/// Each event is formatted when it is sent and written to the
+/// underlying stream before the next event is accepted. Closing the
+/// sink is equivalent to calling [finish](Writer::finish).
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use futures::{stream, SinkExt, StreamExt};
+/// use hepmc2::{Event, Writer};
+///
+/// let mut output = Vec::new();
+/// let mut writer = Writer::new(&mut output).await?;
+/// let events = vec![Event::default(); 2];
+/// stream::iter(events).map(Ok).forward(&mut writer).await?;
+/// # Ok::<(), std::io::Error>(())
+/// # }).unwrap()
+/// ```
+#[cfg(feature = "tokio")]
+#[write_bound]
+impl<'a, T> futures_sink::Sink<&'a Event> for Writer<T> {
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_write_buf(cx)
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        event: &'a Event,
+    ) -> Result<(), Self::Error> {
+        let writer = self.get_mut();
+        writer.format().write_event(&mut writer.buf, event)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let writer = self.get_mut();
+        ready!(writer.poll_write_buf(cx))?;
+        Pin::new(&mut writer.stream).poll_flush(cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_finish(cx)
+    }
+}
+
+/// Writing owned events from an asynchronous stream
+///
+/// This behaves like the implementation for `&Event`.
+#[cfg(feature = "tokio")]
+#[write_bound]
+impl<T> futures_sink::Sink<Event> for Writer<T> {
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures_sink::Sink::<&Event>::poll_ready(self, cx)
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        event: Event,
+    ) -> Result<(), Self::Error> {
+        futures_sink::Sink::<&Event>::start_send(self, &event)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures_sink::Sink::<&Event>::poll_flush(self, cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        futures_sink::Sink::<&Event>::poll_close(self, cx)
     }
 }
 
 #[write_bound]
 impl<T> Drop for Writer<T> {
     fn drop(&mut self) {
-        if !self.finished {
+        if self.finished {
+            return;
+        }
+        let policy = self.on_unfinished;
+        if policy == OnUnfinished::Log {
             error!("Hepmc2 writer dropped before finished.");
             error!("Call finish() manually to fix this error.");
-            #[cfg(feature = "sync")]
-            if let Err(err) = self.ref_finish() {
+        }
+        #[cfg(feature = "sync")]
+        let res = self.ref_finish();
+        #[cfg(feature = "tokio")]
+        let res = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.ref_finish())
+        });
+        if let Err(err) = res {
+            if policy != OnUnfinished::Silent {
                 error!("Error writing footer: {}", err);
             }
-            #[cfg(feature = "tokio")]
-            tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    if let Err(err) = self.ref_finish().await {
-                        error!("Error writing footer: {}", err);
-                    }
-                })
-            });
         }
+        if policy == OnUnfinished::Panic && !std::thread::panicking() {
+            panic!("Hepmc2 writer dropped before finished");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(value: f64, precision: Option<usize>) -> String {
+        Float { value, precision }.to_string()
+    }
+
+    #[test]
+    fn tst_float_precision() {
+        assert_eq!(fmt(0.1, None), "0.1");
+        assert_eq!(fmt(0., Some(3)), "0");
+        assert_eq!(fmt(-0., Some(3)), "0");
+        assert_eq!(fmt(1., Some(3)), "1.00e+00");
+        assert_eq!(fmt(-1234.5678, Some(5)), "-1.2346e+03");
+        assert_eq!(fmt(5.5606e-9, Some(4)), "5.561e-09");
+        assert_eq!(fmt(2.5e123, Some(2)), "2.5e+123");
+        assert_eq!(fmt(7., Some(1)), "7e+00");
+        assert_eq!(fmt(0.1, Some(17)), "1.0000000000000001e-01");
+        assert_eq!(fmt(f64::INFINITY, Some(3)), "inf");
     }
 }